@@ -0,0 +1,269 @@
+//! Use embedded-hal 0.2 I2C buses with drivers written for embedded-hal 1.0.
+//!
+//! ```
+//! use i2c_compat::Compat;
+//!
+//! fn wrap<I2C, E>(bus: I2C) -> impl embedded_hal::i2c::I2c
+//!     where
+//!         I2C: embedded_hal_02::blocking::i2c::Write<Error = E>
+//!             + embedded_hal_02::blocking::i2c::Read<Error = E>
+//!             + embedded_hal_02::blocking::i2c::WriteRead<Error = E>,
+//!         E: core::fmt::Debug
+//! {
+//!     Compat::new(bus)
+//! }
+//! ```
+//!
+//! embedded-hal 0.2 errors carry no kind. Drivers that check the kind, for example to tell
+//! a missing chip from a broken bus, need the kind of the bus error, given with
+//! `Compat::with_error_kind`:
+//!
+//! ```
+//! use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+//! use i2c_compat::Compat;
+//!
+//! #[derive(Debug)]
+//! enum HalError { Nack, Overrun }
+//!
+//! fn wrap<I2C>(bus: I2C) -> impl embedded_hal::i2c::I2c
+//!     where
+//!         I2C: embedded_hal_02::blocking::i2c::Write<Error = HalError>
+//!             + embedded_hal_02::blocking::i2c::Read<Error = HalError>
+//!             + embedded_hal_02::blocking::i2c::WriteRead<Error = HalError>
+//! {
+//!     Compat::with_error_kind(bus, |e: &HalError| match e {
+//!         HalError::Nack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+//!         HalError::Overrun => ErrorKind::Overrun,
+//!     })
+//! }
+//! ```
+
+#![no_std]
+#![deny(trivial_casts)]
+#![deny(trivial_numeric_casts)]
+
+extern crate embedded_hal as hal;
+extern crate embedded_hal_02 as hal02;
+
+use hal::i2c::{ErrorKind, Operation};
+use hal02::blocking::i2c::{Read, Write, WriteRead};
+
+/// Error of the wrapped 0.2 bus, with its kind.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CompatError<E> {
+    pub error: E,
+    pub kind: ErrorKind,
+}
+
+impl<E> hal::i2c::Error for CompatError<E>
+    where
+        E: core::fmt::Debug
+{
+    fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+/// Kind of a 0.2 bus error.
+pub trait MapErrorKind<E> {
+    fn kind(&self, error: &E) -> ErrorKind;
+}
+
+impl<E, F> MapErrorKind<E> for F
+    where
+        F: Fn(&E) -> ErrorKind
+{
+    fn kind(&self, error: &E) -> ErrorKind {
+        self(error)
+    }
+}
+
+/// Maps every error to `ErrorKind::Other`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AllOther;
+
+impl<E> MapErrorKind<E> for AllOther {
+    fn kind(&self, _error: &E) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// embedded-hal 0.2 I2C bus that implements the embedded-hal 1.0 `I2c` trait.
+pub struct Compat<I2C, M = AllOther> {
+    i2c: I2C,
+    map: M,
+}
+
+impl<I2C> Compat<I2C> {
+    /// Wrap bus, its errors are reported as `ErrorKind::Other`.
+    pub fn new(i2c: I2C) -> Compat<I2C> {
+        Compat::with_error_kind(i2c, AllOther)
+    }
+}
+
+impl<I2C, M> Compat<I2C, M> {
+    /// Wrap bus, map gives the kind of its errors.
+    pub fn with_error_kind(i2c: I2C, map: M) -> Compat<I2C, M> {
+        Compat {
+            i2c,
+            map,
+        }
+    }
+
+    /// Get the wrapped bus.
+    pub fn inner(&self) -> &I2C {
+        &self.i2c
+    }
+
+    /// Release the wrapped bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+/// Wrap error with its kind.
+fn compat_error<M, E>(map: &M, error: E) -> CompatError<E>
+    where
+        M: MapErrorKind<E>
+{
+    CompatError {
+        kind: map.kind(&error),
+        error,
+    }
+}
+
+impl<I2C, M, E> hal::i2c::ErrorType for Compat<I2C, M>
+    where
+        I2C: Write<Error = E> + Read<Error = E> + WriteRead<Error = E>,
+        M: MapErrorKind<E>,
+        E: core::fmt::Debug
+{
+    type Error = CompatError<E>;
+}
+
+impl<I2C, M, E> hal::i2c::I2c for Compat<I2C, M>
+    where
+        I2C: Write<Error = E> + Read<Error = E> + WriteRead<Error = E>,
+        M: MapErrorKind<E>,
+        E: core::fmt::Debug
+{
+    fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.i2c.read(address, read).map_err(|e| compat_error(&self.map, e))
+    }
+
+    fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        self.i2c.write(address, write).map_err(|e| compat_error(&self.map, e))
+    }
+
+    fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.i2c.write_read(address, write, read).map_err(|e| compat_error(&self.map, e))
+    }
+
+    /// 0.2 has no transactions, so each operation is a separate transfer with its own stop.
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        for operation in operations {
+            match operation {
+                Operation::Read(read) => self.read(address, read)?,
+                Operation::Write(write) => self.write(address, write)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hal::i2c::{Error, I2c, NoAcknowledgeSource};
+
+    /// 0.2 bus that records writes and reads back a counter.
+    #[derive(Default)]
+    struct Bus {
+        written: [u8; 4],
+        next: u8,
+    }
+
+    impl Write for Bus {
+        type Error = u8;
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), u8> {
+            if address != 0x20 {
+                return Err(address);
+            }
+            self.written[..bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    impl Read for Bus {
+        type Error = u8;
+
+        fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), u8> {
+            if address != 0x20 {
+                return Err(address);
+            }
+            for byte in buffer {
+                *byte = self.next;
+                self.next += 1;
+            }
+            Ok(())
+        }
+    }
+
+    impl WriteRead for Bus {
+        type Error = u8;
+
+        fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), u8> {
+            Write::write(self, address, bytes)?;
+            Read::read(self, address, buffer)
+        }
+    }
+
+    #[test]
+    fn forwards_transfers() {
+        let mut bus = Compat::new(Bus::default());
+        bus.write(0x20, &[1, 2]).unwrap();
+        assert_eq!(bus.inner().written[..2], [1, 2]);
+
+        let mut read = [0; 2];
+        bus.write_read(0x20, &[9], &mut read).unwrap();
+        assert_eq!(read, [0, 1]);
+        assert_eq!(bus.inner().written[0], 9);
+
+        bus.transaction(0x20, &mut [Operation::Write(&[7]), Operation::Read(&mut read)]).unwrap();
+        assert_eq!(read, [2, 3]);
+        assert_eq!(bus.release().written[0], 7);
+    }
+
+    #[test]
+    fn errors_map_to_other() {
+        let mut bus = Compat::new(Bus::default());
+        let error = bus.write(0x21, &[1]).unwrap_err();
+        assert_eq!(error, CompatError { error: 0x21, kind: ErrorKind::Other });
+        assert_eq!(error.kind(), ErrorKind::Other);
+    }
+
+    /// Addresses above 0x20 fail like a bus that reports why.
+    fn kind(address: &u8) -> ErrorKind {
+        match *address {
+            0x21 => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            0x22 => ErrorKind::ArbitrationLoss,
+            0x23 => ErrorKind::Bus,
+            0x24 => ErrorKind::Overrun,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    #[test]
+    fn errors_map_to_kind() {
+        let mut bus = Compat::with_error_kind(Bus::default(), kind);
+        let mut read = [0; 1];
+
+        assert_eq!(bus.write(0x21, &[1]).unwrap_err().kind(), ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        assert_eq!(bus.read(0x22, &mut read).unwrap_err().kind(), ErrorKind::ArbitrationLoss);
+        assert_eq!(bus.write_read(0x23, &[1], &mut read).unwrap_err().kind(), ErrorKind::Bus);
+        let error = bus.transaction(0x24, &mut [Operation::Read(&mut read)]).unwrap_err();
+        assert_eq!(error, CompatError { error: 0x24, kind: ErrorKind::Overrun });
+        assert_eq!(bus.write(0x25, &[1]).unwrap_err().kind(), ErrorKind::Other);
+    }
+}
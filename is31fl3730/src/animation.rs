@@ -0,0 +1,315 @@
+//! Time-driven brightness animation of the PWM register.
+//!
+//! Curves produce perceptual levels (0 - 127) for the elapsed time, which are
+//! converted to PWM duty with the `GAMMA` table. Time only advances with `tick`,
+//! so animations are fully deterministic.
+
+use crate::{Device, DeviceError};
+
+/// Number of perceptual brightness levels.
+pub const LEVELS: usize = 128;
+
+/// Highest perceptual brightness level.
+pub const MAX_LEVEL: u8 = LEVELS as u8 - 1;
+
+/// PWM duty (0 - 128) for each perceptual level, gamma 2.2.
+///
+/// Non-zero levels are never fully off.
+pub const GAMMA: [u8; LEVELS] = [
+      0,   1,   1,   1,   1,   1,   1,   1,   1,   1,   1,   1,   1,   1,   1,   1,
+      1,   2,   2,   2,   2,   2,   3,   3,   3,   4,   4,   4,   5,   5,   5,   6,
+      6,   7,   7,   8,   8,   8,   9,  10,  10,  11,  11,  12,  12,  13,  14,  14,
+     15,  16,  16,  17,  18,  19,  20,  20,  21,  22,  23,  24,  25,  26,  26,  27,
+     28,  29,  30,  31,  32,  33,  35,  36,  37,  38,  39,  40,  41,  43,  44,  45,
+     46,  48,  49,  50,  52,  53,  54,  56,  57,  59,  60,  61,  63,  64,  66,  68,
+     69,  71,  72,  74,  76,  77,  79,  81,  82,  84,  86,  88,  90,  91,  93,  95,
+     97,  99, 101, 103, 105, 107, 109, 111, 113, 115, 117, 119, 121, 124, 126, 128,
+];
+
+/// Convert perceptual level (0 - 127) to PWM duty (0 - 128).
+pub fn gamma(level: u8) -> u8 {
+    GAMMA[level.min(MAX_LEVEL) as usize]
+}
+
+/// Brightness over time, in perceptual levels (0 - 127).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Curve {
+    /// Hold the level.
+    Constant(u8),
+    /// Fade at constant rate, then hold the final level.
+    Linear { from: u8, to: u8, duration_ms: u32 },
+    /// Fade slowly at both ends and faster in the middle, then hold the final level.
+    Eased { from: u8, to: u8, duration_ms: u32 },
+    /// Repeat eased fade from min to max and back.
+    Breathe { min: u8, max: u8, period_ms: u32 },
+    /// Repeat on level followed by off level.
+    Blink { on: u8, off: u8, on_ms: u32, off_ms: u32 },
+}
+
+/// Fixed-point one for curve progress.
+const ONE: u32 = 1 << 10;
+
+impl Curve {
+    /// Level at the time since the start of the curve.
+    pub fn level(&self, time_ms: u32) -> u8 {
+        match *self {
+            Curve::Constant(level) => level,
+            Curve::Linear { from, to, duration_ms } =>
+                lerp(from, to, progress(time_ms, duration_ms)),
+            Curve::Eased { from, to, duration_ms } =>
+                lerp(from, to, smoothstep(progress(time_ms, duration_ms))),
+            Curve::Breathe { min, max, period_ms } => {
+                if period_ms == 0 {
+                    return max;
+                }
+                let half = period_ms / 2;
+                let phase = time_ms % period_ms;
+                let x = if phase < half {
+                    progress(phase, half)
+                } else {
+                    progress(period_ms - phase, period_ms - half)
+                };
+                lerp(min, max, smoothstep(x))
+            },
+            Curve::Blink { on, off, on_ms, off_ms } => {
+                let period_ms = on_ms as u64 + off_ms as u64;
+                if period_ms == 0 || (time_ms as u64 % period_ms) < on_ms as u64 { on } else { off }
+            },
+        }
+    }
+
+    /// Check if the curve holds the same level from this time on.
+    pub fn is_finished(&self, time_ms: u32) -> bool {
+        match *self {
+            Curve::Constant(_) => true,
+            Curve::Linear { duration_ms, .. } | Curve::Eased { duration_ms, .. } => time_ms >= duration_ms,
+            Curve::Breathe { .. } | Curve::Blink { .. } => false,
+        }
+    }
+
+    /// Time after which the curve repeats itself, `None` if it does not.
+    pub fn period_ms(&self) -> Option<u32> {
+        let period_ms = match *self {
+            Curve::Breathe { period_ms, .. } => Some(period_ms),
+            Curve::Blink { on_ms, off_ms, .. } => on_ms.checked_add(off_ms),
+            _ => None,
+        };
+        period_ms.filter(|&period_ms| period_ms > 0)
+    }
+}
+
+/// Progress of time over duration, 0 - ONE.
+fn progress(time_ms: u32, duration_ms: u32) -> u32 {
+    if time_ms >= duration_ms {
+        ONE
+    } else {
+        (time_ms as u64 * ONE as u64 / duration_ms as u64) as u32
+    }
+}
+
+/// Ease-in-out of progress, 0 - ONE.
+fn smoothstep(x: u32) -> u32 {
+    let x = x as u64;
+    let one = ONE as u64;
+    (x * x * (3 * one - 2 * x) / (one * one)) as u32
+}
+
+fn lerp(from: u8, to: u8, x: u32) -> u8 {
+    let from = from as i32;
+    let to = to as i32;
+    let offset = (to - from) * x as i32;
+    let rounded = (offset + if offset < 0 { -(ONE as i32) / 2 } else { ONE as i32 / 2 }) / ONE as i32;
+    (from + rounded) as u8
+}
+
+/// Drives the PWM register of a single device along a curve.
+///
+/// ```
+/// use is31fl3730::animation::{Animator, Curve};
+///
+/// let mut animator = Animator::new(Curve::Linear { from: 0, to: 127, duration_ms: 1000 });
+/// assert_eq!(animator.tick(0), Some(0));
+/// assert_eq!(animator.tick(500), Some(28));
+/// assert_eq!(animator.tick(0), None);
+/// assert_eq!(animator.tick(500), Some(128));
+/// assert!(animator.is_finished());
+/// ```
+#[derive(Copy, Clone)]
+pub struct Animator {
+    curve: Curve,
+    time_ms: u32,
+    pwm: Option<u8>,
+}
+
+impl Animator {
+    pub fn new(curve: Curve) -> Animator {
+        Animator {
+            curve,
+            time_ms: 0,
+            pwm: None,
+        }
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    /// Replace the curve and start it from the beginning.
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+        self.time_ms = 0;
+    }
+
+    /// Time since the start of the curve, within the period for repeating curves.
+    pub fn time_ms(&self) -> u32 {
+        self.time_ms
+    }
+
+    /// Last PWM duty returned by tick.
+    pub fn pwm(&self) -> Option<u8> {
+        self.pwm
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.curve.is_finished(self.time_ms)
+    }
+
+    /// Advance time and return the new PWM duty if it changed.
+    pub fn tick(&mut self, elapsed_ms: u32) -> Option<u8> {
+        let time_ms = self.time_ms as u64 + elapsed_ms as u64;
+        self.time_ms = match self.curve.period_ms() {
+            // Repeating curves keep running past the range of the timer.
+            Some(period_ms) => (time_ms % period_ms as u64) as u32,
+            None => time_ms.min(u32::MAX as u64) as u32,
+        };
+        let pwm = gamma(self.curve.level(self.time_ms));
+
+        if self.pwm == Some(pwm) {
+            return None;
+        }
+
+        self.pwm = Some(pwm);
+        Some(pwm)
+    }
+
+    /// Advance time and send the PWM duty to device if it changed.
+    pub fn run<I2C, E>(&mut self, device: &mut Device<I2C>, elapsed_ms: u32) -> Result<(), DeviceError<E>>
+        where
+            I2C: hal::i2c::I2c<Error = E>
+    {
+        if let Some(pwm) = self.tick(elapsed_ms) {
+            if let Err(e) = device.set_pwm(pwm) {
+                self.pwm = None;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Address;
+    use crate::sim::Chip;
+
+    #[test]
+    fn gamma_is_monotonic() {
+        assert_eq!(GAMMA[0], 0);
+        assert_eq!(GAMMA[LEVELS - 1], 128);
+        for pair in GAMMA.windows(2) {
+            assert!(pair[0] <= pair[1]);
+        }
+        assert!(GAMMA[1..].iter().all(|&pwm| pwm > 0));
+    }
+
+    #[test]
+    fn linear_fade() {
+        let curve = Curve::Linear { from: 100, to: 20, duration_ms: 800 };
+        assert_eq!(curve.level(0), 100);
+        assert_eq!(curve.level(400), 60);
+        assert_eq!(curve.level(800), 20);
+        assert_eq!(curve.level(5000), 20);
+        assert!(!curve.is_finished(799));
+        assert!(curve.is_finished(800));
+    }
+
+    #[test]
+    fn eased_fade() {
+        let curve = Curve::Eased { from: 0, to: 100, duration_ms: 1000 };
+        assert_eq!(curve.level(0), 0);
+        assert_eq!(curve.level(500), 50);
+        assert_eq!(curve.level(1000), 100);
+        assert!(curve.level(100) < 10);
+        assert!(curve.level(900) > 90);
+        assert_eq!(curve.level(250) + curve.level(750), 100);
+
+        let mut previous = 0;
+        for time_ms in 0..1000 {
+            let level = curve.level(time_ms);
+            assert!(level >= previous);
+            previous = level;
+        }
+    }
+
+    #[test]
+    fn breathe_repeats() {
+        let curve = Curve::Breathe { min: 10, max: 110, period_ms: 2000 };
+        assert_eq!(curve.level(0), 10);
+        assert_eq!(curve.level(1000), 110);
+        assert_eq!(curve.level(2000), 10);
+        assert_eq!(curve.level(500), curve.level(1500));
+        assert_eq!(curve.level(700), curve.level(4700));
+        assert!(!curve.is_finished(100_000));
+    }
+
+    #[test]
+    fn blink_alternates() {
+        let curve = Curve::Blink { on: 127, off: 0, on_ms: 100, off_ms: 300 };
+        assert_eq!(curve.level(0), 127);
+        assert_eq!(curve.level(99), 127);
+        assert_eq!(curve.level(100), 0);
+        assert_eq!(curve.level(399), 0);
+        assert_eq!(curve.level(400), 127);
+    }
+
+    #[test]
+    fn repeating_curves_keep_running() {
+        let curve = Curve::Blink { on: 127, off: 0, on_ms: u32::MAX, off_ms: u32::MAX };
+        assert_eq!(curve.period_ms(), None);
+        assert_eq!(curve.level(u32::MAX), 0);
+
+        let mut animator = Animator::new(Curve::Blink { on: 127, off: 0, on_ms: 100, off_ms: 100 });
+        animator.tick(u32::MAX - 50);
+        assert_eq!(animator.time_ms(), (u32::MAX - 50) % 200);
+        let mut changes = 0;
+        for _ in 0..10 {
+            changes += animator.tick(50).is_some() as u32;
+        }
+        assert!(changes >= 2);
+
+        let mut animator = Animator::new(Curve::Breathe { min: 0, max: 127, period_ms: 2000 });
+        animator.tick(u32::MAX);
+        assert!(animator.time_ms() < 2000);
+        assert_ne!(animator.tick(1000), None);
+    }
+
+    #[test]
+    fn animator_writes_pwm_only_on_change() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        let mut animator = Animator::new(Curve::Blink { on: 127, off: 0, on_ms: 100, off_ms: 100 });
+
+        for _ in 0..19 {
+            animator.run(&mut device, 10).unwrap();
+        }
+
+        assert_eq!(device.i2c().write_count(), 2);
+        assert_eq!(device.i2c().pwm_duty(), 0);
+
+        animator.set_curve(Curve::Constant(MAX_LEVEL));
+        animator.run(&mut device, 10).unwrap();
+        animator.run(&mut device, 10).unwrap();
+        assert_eq!(device.i2c().write_count(), 3);
+        assert_eq!(device.i2c().pwm_duty(), 128);
+    }
+}
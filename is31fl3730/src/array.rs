@@ -0,0 +1,243 @@
+//! Several chips presented as one display.
+//!
+//! Matrices are tiled left to right, first and second matrix of the first device,
+//! then of the next device, and so on. All devices share the same matrix mode.
+
+use crate::{Device, DeviceError, Brightness, Configuration, ConfigMatrixMode, ConfigDisplayMode, Frame, Lighting};
+use crate::pixels::DataBits;
+
+/// Error of a single device in the array.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ArrayError<E> {
+    /// Index of the device.
+    pub index: usize,
+    pub error: DeviceError<E>,
+}
+
+/// `N` devices, on one or several buses, driven as a single framebuffer.
+///
+/// All devices have the same bus type `I2C`. Chips on one bus each need their own handle
+/// to it, for example a `RefCell` proxy like `embedded_hal_bus::i2c::RefCellDevice`.
+/// Chips on buses of different types need a bus type that forwards to either of them.
+///
+/// The example needs the simulator of the `std` feature.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use embedded_hal::i2c::{ErrorType, I2c, Operation};
+/// use is31fl3730::{Address, ConfigMatrixMode, Device};
+/// use is31fl3730::array::DeviceArray;
+/// use is31fl3730::sim::{Chip, Error};
+/// use is31fl3730::transcript::Recorder;
+///
+/// enum Bus {
+///     Plain(Chip),
+///     Recorded(Recorder<Chip>),
+/// }
+///
+/// impl ErrorType for Bus {
+///     type Error = Error;
+/// }
+///
+/// impl I2c for Bus {
+///     fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+///         match self {
+///             Bus::Plain(chip) => chip.transaction(address, operations),
+///             Bus::Recorded(recorder) => recorder.transaction(address, operations),
+///         }
+///     }
+/// }
+///
+/// let mut array = DeviceArray::new(ConfigMatrixMode::Size8x8, [
+///     Device::new(Address::Address00, Bus::Plain(Chip::new(Address::Address00))),
+///     Device::new(Address::Address01, Bus::Recorded(Recorder::new(Chip::new(Address::Address01)))),
+/// ]);
+/// array.init().unwrap();
+/// assert_eq!(array.width(), 32);
+/// ```
+pub struct DeviceArray<I2C, const N: usize>
+    where
+        I2C: hal::i2c::I2c,
+{
+    devices: [Device<I2C>; N],
+    matrix_mode: ConfigMatrixMode,
+}
+
+impl<I2C, E, const N: usize> DeviceArray<I2C, N>
+    where
+        I2C: hal::i2c::I2c<Error = E>,
+{
+    /// Devices are tiled in the order given, the first one leftmost.
+    pub fn new(matrix_mode: ConfigMatrixMode, devices: [Device<I2C>; N]) -> DeviceArray<I2C, N> {
+        DeviceArray {
+            devices,
+            matrix_mode,
+        }
+    }
+
+    pub fn devices(&self) -> &[Device<I2C>] {
+        &self.devices
+    }
+
+    pub fn device_mut(&mut self, index: usize) -> Option<&mut Device<I2C>> {
+        self.devices.get_mut(index)
+    }
+
+    /// Release all devices.
+    pub fn release(self) -> [Device<I2C>; N] {
+        self.devices
+    }
+
+    pub fn matrix_mode(&self) -> ConfigMatrixMode {
+        self.matrix_mode
+    }
+
+    /// Width of the framebuffer in pixels.
+    pub fn width(&self) -> usize {
+        self.matrix_mode.width() * 2 * N
+    }
+
+    /// Height of the framebuffer in pixels.
+    pub fn height(&self) -> usize {
+        self.matrix_mode.height()
+    }
+
+    /// Reset all devices and configure them to show both matrices in the array matrix mode.
+    pub fn init(&mut self) -> Result<(), ArrayError<E>> {
+        let matrix_mode = self.matrix_mode;
+        self.each(|device| {
+            device.reset()?;
+            device.modify_config(|c| c
+                .set_matrix_mode(matrix_mode)
+                .set_display_mode(ConfigDisplayMode::Matrix1and2))
+        })
+    }
+
+    /// Modify configuration of all devices. The matrix mode always stays the array matrix mode.
+    pub fn modify_config<F>(&mut self, mut modify: F) -> Result<(), ArrayError<E>>
+        where F: FnMut(&mut Configuration) -> &mut Configuration {
+        let matrix_mode = self.matrix_mode;
+        self.each(|device| device.modify_config(|c| modify(c).set_matrix_mode(matrix_mode)))
+    }
+
+    /// Modify lighting configuration of all devices.
+    pub fn modify_lighting<F>(&mut self, mut modify: F) -> Result<(), ArrayError<E>>
+        where F: FnMut(&mut Lighting) -> &mut Lighting {
+        self.each(|device| device.modify_lighting(&mut modify))
+    }
+
+    /// Set PWM duty of all devices.
+    pub fn set_pwm(&mut self, value: u8) -> Result<(), ArrayError<E>> {
+        self.each(|device| device.set_pwm(value))
+    }
+
+    /// Set brightness of all devices.
+    pub fn set_brightness(&mut self, brightness: Brightness) -> Result<(), ArrayError<E>> {
+        self.each(|device| device.set_brightness(brightness))
+    }
+
+    /// Output the top-left `width` x `height` pixels of data to all matrices.
+    ///
+    /// Each device is sent only changed rows, followed by an update if anything changed.
+    pub fn output_pixels<DATA>(&mut self, data: &DATA) -> Result<(), ArrayError<E>>
+        where
+            DATA: DataBits
+    {
+        let matrix_mode = self.matrix_mode;
+        let mut x = 0;
+
+        self.each(|device| {
+            let frame = Frame::from_data_at(matrix_mode, data, x);
+            x += matrix_mode.width() as i16 * 2;
+            device.present(&frame)
+        })
+    }
+
+    /// Run operation on every device, even if some fail. Returns the first error.
+    fn each<F>(&mut self, mut operation: F) -> Result<(), ArrayError<E>>
+        where F: FnMut(&mut Device<I2C>) -> Result<(), DeviceError<E>> {
+        let mut result = Ok(());
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            if let Err(error) = operation(device) {
+                if result.is_ok() {
+                    result = Err(ArrayError { index, error });
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Address;
+    use crate::sim::{self, Chip};
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W24, H16};
+
+    fn array<const N: usize>(addresses: [Address; N]) -> DeviceArray<Chip, N> {
+        DeviceArray::new(ConfigMatrixMode::Size5x11, addresses.map(|address| Device::new(address, Chip::new(address))))
+    }
+
+    #[test]
+    fn framebuffer_spans_all_matrices() {
+        let mut array = array([Address::Address00, Address::Address01]);
+        array.init().unwrap();
+        assert_eq!((array.width(), array.height()), (20, 11));
+
+        let mut canvas = BitCanvas::<W24, H16>::new(24, 11).unwrap();
+        // Pixels 0, 5, 10 and 19 of the first row, pixel 12 of the last row.
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1000_0100, 0b0010_0000, 0b0001_0000]);
+        canvas.row_mut(10).unwrap().copy_from_slice(&[0, 0b0000_1000, 0]);
+        array.output_pixels(&canvas).unwrap();
+
+        let chips: [&Chip; 2] = [array.devices()[0].i2c(), array.devices()[1].i2c()];
+        assert!(chips[0].visible_matrix1().pixel(0, 0));
+        assert!(chips[0].visible_matrix2().pixel(0, 0));
+        assert!(chips[1].visible_matrix1().pixel(0, 0));
+        assert!(chips[1].visible_matrix1().pixel(2, 10));
+        assert!(chips[1].visible_matrix2().pixel(4, 0));
+        let lit: u32 = chips.iter()
+            .map(|c| c.visible_matrix1().lit_count() + c.visible_matrix2().lit_count())
+            .sum();
+        assert_eq!(lit, 5);
+    }
+
+    #[test]
+    fn unchanged_frame_is_not_sent() {
+        let mut array = array([Address::Address00, Address::Address01]);
+        array.init().unwrap();
+
+        let mut canvas = BitCanvas::<W24, H16>::new(24, 11).unwrap();
+        canvas.row_mut(3).unwrap().copy_from_slice(&[0, 0, 0b0001_0000]);
+        array.output_pixels(&canvas).unwrap();
+        let writes = array.devices()[0].i2c().write_count();
+        assert_eq!(array.devices()[1].i2c().write_count(), writes + 2);
+
+        array.output_pixels(&canvas).unwrap();
+        assert_eq!(array.devices()[0].i2c().write_count(), writes);
+        assert_eq!(array.devices()[1].i2c().write_count(), writes + 2);
+    }
+
+    #[test]
+    fn broadcast_continues_past_failed_device() {
+        let mut array = DeviceArray::new(ConfigMatrixMode::Size5x11, [
+            Device::new(Address::Address00, Chip::new(Address::Address00)),
+            Device::new(Address::Address01, Chip::new(Address::Address11)),
+            Device::new(Address::Address10, Chip::new(Address::Address10)),
+        ]);
+
+        let error = array.set_pwm(64).unwrap_err();
+        assert_eq!(error, ArrayError { index: 1, error: DeviceError::Bus(sim::Error::Nack(Address::Address01 as u8)) });
+        assert_eq!(array.devices()[0].i2c().pwm_duty(), 64);
+        assert_eq!(array.devices()[2].i2c().pwm_duty(), 64);
+    }
+
+    #[test]
+    fn release_returns_devices_in_order() {
+        let array = array([Address::Address00, Address::Address01, Address::Address10, Address::Address11]);
+        let addresses = array.release().map(|device| device.address());
+        assert_eq!(addresses, [Address::Address00, Address::Address01, Address::Address10, Address::Address11]);
+    }
+}
@@ -0,0 +1,225 @@
+//! Async driver over `embedded-hal-async`, enabled with the `async` feature.
+//!
+//! Sends the same register writes as the blocking `Device`, so the bus can be
+//! shared with other tasks while a frame is pushed.
+
+use embedded_hal_async::i2c::I2c;
+use crate::{Address, Configuration, Lighting, DeviceError, command};
+use crate::register::Register;
+use crate::shadow::{self, Shadow};
+use crate::display::{MatrixBank, OutputRows};
+use crate::pixels::DataBits;
+
+pub struct Device<I2C>
+    where
+        I2C: I2c,
+{
+    address: Address,
+    i2c: I2C,
+    shadow: Shadow,
+}
+
+impl<I2C, E> Device<I2C>
+    where
+        I2C: I2c<Error = E>,
+{
+    pub fn new(address: Address, i2c: I2C) -> Device<I2C> {
+        Device {
+            address,
+            i2c,
+            shadow: Shadow::default(),
+        }
+    }
+
+    /// Get the underlying bus.
+    pub fn i2c(&self) -> &I2C {
+        &self.i2c
+    }
+
+    /// Release the underlying bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Set PWM duty in 1/128 steps (0 - 128), 128 is full brightness.
+    pub async fn set_pwm(&mut self, value: u8) -> Result<(), DeviceError<E>> {
+        let value = command::pwm_register(value)?;
+        self.write(&command::pwm(value)).await?;
+        self.shadow.pwm = value;
+        Ok(())
+    }
+
+    /// Get PWM duty (0 - 128).
+    pub fn pwm(&self) -> u8 {
+        if self.shadow.pwm & 0b1000_0000 > 0 { 128 } else { self.shadow.pwm }
+    }
+
+    /// Write pixels for the first matrix. Call update to flush updates.
+    pub async fn set_matrix1_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        command::check_rows(start_row, rows)?;
+        self.write_rows(Register::Matrix1Begin, start_row, rows).await?;
+        shadow::copy_rows(&mut self.shadow.matrix1, start_row as usize, rows);
+        Ok(())
+    }
+
+    /// Write pixels for the second matrix. Call update to flush updates.
+    pub async fn set_matrix2_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        command::check_rows(start_row, rows)?;
+        self.write_rows(Register::Matrix2Begin, start_row, rows).await?;
+        shadow::copy_rows(&mut self.shadow.matrix2, start_row as usize, rows);
+        Ok(())
+    }
+
+    /// Get first matrix rows, as sent.
+    pub fn matrix1_rows(&self) -> &[u8] {
+        &self.shadow.matrix1
+    }
+
+    /// Get second matrix rows, as sent.
+    pub fn matrix2_rows(&self) -> &[u8] {
+        &self.shadow.matrix2
+    }
+
+    /// Flush display updates.
+    pub async fn update(&mut self) -> Result<(), DeviceError<E>> {
+        self.write(&command::update()).await
+    }
+
+    /// Reset device.
+    pub async fn reset(&mut self) -> Result<(), DeviceError<E>> {
+        self.write(&command::reset()).await?;
+        self.shadow = Shadow::default();
+        Ok(())
+    }
+
+    /// Get lighting configuration.
+    pub fn lighting(&self) -> Lighting {
+        self.shadow.lighting
+    }
+
+    /// Modify lighting configuration and send it to device.
+    pub async fn modify_lighting<F>(&mut self, mut modify: F) -> Result<(), DeviceError<E>>
+        where F: FnMut(&mut Lighting) -> &mut Lighting {
+        let mut lighting = self.shadow.lighting;
+        modify(&mut lighting);
+        self.write(&command::lighting(lighting)).await?;
+        self.shadow.lighting = lighting;
+        Ok(())
+    }
+
+    /// Get device configuration.
+    pub fn config(&self) -> Configuration {
+        self.shadow.config
+    }
+
+    /// Modify device configuration and send it to device.
+    pub async fn modify_config<F>(&mut self, mut modify: F) -> Result<(), DeviceError<E>>
+        where F: FnMut(&mut Configuration) -> &mut Configuration {
+        let mut config = self.shadow.config;
+        modify(&mut config);
+        self.write(&command::config(config)).await?;
+        self.shadow.config = config;
+        Ok(())
+    }
+
+    /// Output pixels to target matrix. Call update to flush updates.
+    pub async fn output_pixels<T, DATA>(&mut self, target: &T, data: &DATA) -> Result<(), DeviceError<E>>
+        where
+            T: OutputRows,
+            DATA: DataBits
+    {
+        if self.shadow.config.matrix_mode() != T::MATRIX_MODE {
+            return Err(DeviceError::MatrixModeMismatch);
+        }
+
+        let mut buffer: [u8; 11] = [0; 11];
+        target.pack_rows(data, &mut buffer);
+
+        let rows = &buffer[0..T::HEIGHT];
+        match T::BANK {
+            MatrixBank::Matrix1 => self.set_matrix1_rows(0, rows).await,
+            MatrixBank::Matrix2 => self.set_matrix2_rows(0, rows).await,
+        }
+    }
+
+    async fn write_rows(&mut self, begin: Register, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        self.write(command::RowsCommand::new(begin, start_row, rows).bytes()).await
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), DeviceError<E>> {
+        self.i2c.write(self.address as u8, bytes).await.map_err(DeviceError::Bus)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::future::Future;
+    use core::task::{Context, Poll, Waker};
+    use crate::{ConfigMatrixMode, ConfigDisplayMode, LightingCurrent};
+    use crate::display::MatrixTargetSecondary5x11;
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W8, H16};
+
+    /// Run a future that never waits, the simulated bus completes immediately.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn sends_same_bytes_as_blocking_driver() {
+        let mut blocking = crate::Device::new(Address::Address00, Chip::new(Address::Address00));
+        blocking.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        blocking.modify_lighting(|l| l.set_current(LightingCurrent::Current20mA)).unwrap();
+        blocking.set_pwm(64).unwrap();
+        blocking.set_matrix2_rows(3, &[0xaa, 0x55]).unwrap();
+        blocking.update().unwrap();
+
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        block_on(async {
+            device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).await.unwrap();
+            device.modify_lighting(|l| l.set_current(LightingCurrent::Current20mA)).await.unwrap();
+            device.set_pwm(64).await.unwrap();
+            device.set_matrix2_rows(3, &[0xaa, 0x55]).await.unwrap();
+            device.update().await.unwrap();
+        });
+
+        let (expected, chip) = (blocking.i2c(), device.i2c());
+        assert_eq!(chip.config(), expected.config());
+        assert_eq!(chip.lighting(), expected.lighting());
+        assert_eq!(chip.pwm(), expected.pwm());
+        assert_eq!(chip.visible_matrix2(), expected.visible_matrix2());
+        assert_eq!(chip.byte_count(), expected.byte_count());
+        assert_eq!(device.pwm(), 64);
+    }
+
+    #[test]
+    fn output_frame() {
+        let mut canvas = BitCanvas::<W8, H16>::new(8, 11).unwrap();
+        canvas.row_mut(10).unwrap().copy_from_slice(&[0b1000_0000]);
+
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        block_on(async {
+            let target = MatrixTargetSecondary5x11 {};
+            assert_eq!(device.output_pixels(&target, &canvas).await, Err(DeviceError::MatrixModeMismatch));
+
+            device.modify_config(|c| c
+                .set_matrix_mode(ConfigMatrixMode::Size5x11)
+                .set_display_mode(ConfigDisplayMode::Matrix2Only)).await.unwrap();
+            device.output_pixels(&target, &canvas).await.unwrap();
+            device.update().await.unwrap();
+        });
+
+        let visible = device.i2c().visible_matrix2();
+        assert!(visible.pixel(0, 10));
+        assert_eq!(visible.lit_count(), 1);
+        assert_eq!(device.matrix2_rows()[10], 0b1_0000);
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{LightingAudioGain, LightingCurrent};
+
+/// Audio gain and row current for audio-modulated intensity.
+///
+/// The amplified audio input modulates the row intensity, with the row current as the
+/// full scale. The datasheet places no constraint on combining gain and current, so every
+/// pair is accepted.
+///
+/// ```
+/// use is31fl3730::{AudioSettings, LightingAudioGain, LightingCurrent};
+///
+/// let settings = AudioSettings::new(LightingAudioGain::Gain6dB, LightingCurrent::Current40mA);
+/// assert_eq!(settings.gain(), LightingAudioGain::Gain6dB);
+/// assert_eq!(settings.current(), LightingCurrent::Current40mA);
+/// ```
+#[derive(Copy, Clone)]
+pub struct AudioSettings {
+    gain: LightingAudioGain,
+    current: LightingCurrent,
+}
+
+impl AudioSettings {
+    pub fn new(gain: LightingAudioGain, current: LightingCurrent) -> AudioSettings {
+        AudioSettings {
+            gain,
+            current,
+        }
+    }
+
+    pub fn gain(&self) -> LightingAudioGain {
+        self.gain
+    }
+
+    pub fn current(&self) -> LightingCurrent {
+        self.current
+    }
+}
@@ -0,0 +1,243 @@
+//! Pre-encoded writes for interrupt or DMA driven I2C.
+//!
+//! A `Batch` fills a caller-provided list of transfers, each an address and a payload
+//! that is sent as a single write. Once encoded, the transfers can be sent without
+//! calling back into the driver.
+
+use crate::{Address, ConfigMatrixMode, Frame};
+use crate::command::{self, RowsCommand};
+use crate::register::Register;
+use crate::shadow::MATRIX_ROWS;
+
+/// Largest payload: register address and all matrix rows.
+pub const MAX_PAYLOAD: usize = 1 + MATRIX_ROWS;
+
+/// Transfers of a frame update of one chip: both matrices and update.
+pub const FRAME_TRANSFERS: usize = 3;
+
+/// Most transfers of a frame update of one chip with a current limit: row current and
+/// PWM duty before and after the frame.
+pub const LIMITED_FRAME_TRANSFERS: usize = FRAME_TRANSFERS + 4;
+
+/// A single write.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Transfer {
+    address: u8,
+    payload: [u8; MAX_PAYLOAD],
+    len: u8,
+}
+
+impl Transfer {
+    fn new(address: Address, bytes: &[u8]) -> Transfer {
+        let mut payload = [0; MAX_PAYLOAD];
+        payload[..bytes.len()].copy_from_slice(bytes);
+
+        Transfer {
+            address: address as u8,
+            payload,
+            len: bytes.len() as u8,
+        }
+    }
+
+    /// 7 bit address.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Bytes to write.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.len as usize]
+    }
+}
+
+/// Transfer list has no room for the encoded writes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BatchFull;
+
+/// Transfers encoded into a caller-provided list, in sending order.
+///
+/// ```
+/// use is31fl3730::{Address, ConfigMatrixMode, Frame};
+/// use is31fl3730::batch::{Batch, Transfer, FRAME_TRANSFERS};
+///
+/// let mut transfers = [Transfer::default(); 2 * FRAME_TRANSFERS];
+/// let mut batch = Batch::new(&mut transfers);
+/// batch.push_frame(Address::Address11, ConfigMatrixMode::Size8x8, &Frame::new()).unwrap();
+/// batch.push_frame(Address::Address01, ConfigMatrixMode::Size8x8, &Frame::new()).unwrap();
+///
+/// for transfer in batch.transfers() {
+///     // Hand transfer.address() and transfer.payload() to the I2C engine.
+/// }
+/// assert_eq!(batch.len(), 6);
+/// ```
+pub struct Batch<'a> {
+    transfers: &'a mut [Transfer],
+    len: usize,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(transfers: &'a mut [Transfer]) -> Batch<'a> {
+        Batch {
+            transfers,
+            len: 0,
+        }
+    }
+
+    /// Encoded transfers.
+    pub fn transfers(&self) -> &[Transfer] {
+        &self.transfers[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of transfers that still fit.
+    pub fn remaining(&self) -> usize {
+        self.transfers.len() - self.len
+    }
+
+    /// Drop encoded transfers to reuse the list.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Encode both matrices of frame, limited to the rows of the matrix mode, followed by update.
+    ///
+    /// Nothing is encoded unless all `FRAME_TRANSFERS` transfers fit.
+    pub fn push_frame(&mut self, address: Address, matrix_mode: ConfigMatrixMode, frame: &Frame) -> Result<(), BatchFull> {
+        if self.remaining() < FRAME_TRANSFERS {
+            return Err(BatchFull);
+        }
+
+        let height = matrix_mode.height();
+        self.push(address, RowsCommand::new(Register::Matrix1Begin, 0, &frame.matrix1()[..height]).bytes())?;
+        self.push(address, RowsCommand::new(Register::Matrix2Begin, 0, &frame.matrix2()[..height]).bytes())?;
+        self.push(address, &command::update())
+    }
+
+    /// Drop transfers encoded after the first `len`.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    pub(crate) fn push(&mut self, address: Address, bytes: &[u8]) -> Result<(), BatchFull> {
+        let transfer = self.transfers.get_mut(self.len).ok_or(BatchFull)?;
+        *transfer = Transfer::new(address, bytes);
+        self.len += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ConfigDisplayMode, CurrentLimit, Device};
+    use crate::sim::Bus;
+    use crate::sim::Chip;
+    use hal::i2c::I2c;
+
+    #[test]
+    fn frames_fit_or_fail_whole() {
+        let mut frame = Frame::new();
+        frame.matrix1_mut()[0] = 0b1000_0001;
+        frame.matrix2_mut()[10] = 0b1_0000;
+
+        let mut transfers = [Transfer::default(); 5];
+        let mut batch = Batch::new(&mut transfers);
+        batch.push_frame(Address::Address10, ConfigMatrixMode::Size5x11, &frame).unwrap();
+        assert_eq!(batch.push_frame(Address::Address00, ConfigMatrixMode::Size5x11, &frame), Err(BatchFull));
+        assert_eq!(batch.len(), 3);
+
+        let transfers = batch.transfers();
+        assert!(transfers.iter().all(|t| t.address() == Address::Address10 as u8));
+        assert_eq!(transfers[0].payload(), &[0x01, 0b1000_0001, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(transfers[1].payload()[11], 0b1_0000);
+        assert_eq!(transfers[2].payload(), &[0x0c, 0]);
+
+        batch.clear();
+        batch.push_frame(Address::Address00, ConfigMatrixMode::Size8x8, &frame).unwrap();
+        assert_eq!(batch.transfers()[0].payload().len(), 9);
+    }
+
+    #[test]
+    fn sent_transfers_show_frames_on_every_chip() {
+        let mut bus = Bus::new();
+        let addresses = [Address::Address11, Address::Address01];
+        for &address in addresses.iter() {
+            let mut device = Device::new(address, Chip::new(address));
+            device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+            bus.plug(device.release());
+        }
+
+        let mut frame = Frame::new();
+        frame.matrix1_mut()[7] = 0xff;
+        frame.matrix2_mut()[0] = 0b0000_0011;
+
+        let mut transfers = [Transfer::default(); 2 * FRAME_TRANSFERS];
+        let mut batch = Batch::new(&mut transfers);
+        for &address in addresses.iter() {
+            batch.push_frame(address, ConfigMatrixMode::Size8x8, &frame).unwrap();
+        }
+
+        for transfer in batch.transfers() {
+            bus.write(transfer.address(), transfer.payload()).unwrap();
+        }
+        for &address in addresses.iter() {
+            let chip = bus.chip(address).unwrap();
+            assert_eq!(chip.visible_matrix1().lit_count(), 8);
+            assert_eq!(chip.visible_matrix2().lit_count(), 2);
+        }
+    }
+
+    #[test]
+    fn device_records_encoded_frame() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        let mut frame = Frame::new();
+        frame.matrix2_mut()[3] = 0x18;
+
+        let mut transfers = [Transfer::default(); FRAME_TRANSFERS];
+        let mut batch = Batch::new(&mut transfers);
+        device.encode_frame(&frame, &mut batch).unwrap();
+        assert_eq!(device.matrix2_rows()[3], 0x18);
+        assert!(!device.is_dirty());
+        assert_eq!(device.encode_frame(&frame, &mut batch), Err(BatchFull));
+
+        // Presenting the same frame afterwards sends nothing.
+        device.present(&frame).unwrap();
+        assert_eq!(device.i2c().write_count(), 0);
+    }
+
+    #[test]
+    fn limited_frame_encodes_output() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.set_pwm(128).unwrap();
+        device.set_current_limit(Some(CurrentLimit::new(20)));
+        let mut frame = Frame::new();
+        frame.matrix1_mut()[..4].copy_from_slice(&[0xff; 4]);
+
+        let mut transfers = [Transfer::default(); LIMITED_FRAME_TRANSFERS];
+        let mut batch = Batch::new(&mut transfers[..FRAME_TRANSFERS]);
+        assert_eq!(device.encode_frame(&frame, &mut batch), Err(BatchFull));
+        assert!(batch.is_empty());
+        assert_eq!(device.pwm(), 128);
+        assert_eq!(device.matrix1_rows()[0], 0);
+
+        let mut batch = Batch::new(&mut transfers);
+        device.encode_frame(&frame, &mut batch).unwrap();
+        assert!(device.pwm() < 128);
+        assert_eq!(device.requested_output().1, 128);
+
+        let pwm = device.pwm();
+        let mut chip = device.release();
+        for transfer in batch.transfers() {
+            chip.write(transfer.address(), transfer.payload()).unwrap();
+        }
+        assert_eq!(chip.pwm_duty(), pwm);
+        assert_eq!(chip.visible_matrix1().lit_count(), 32);
+    }
+}
@@ -0,0 +1,114 @@
+//! Perceptual brightness that combines row current and PWM duty.
+//!
+//! Light output is proportional to `current × duty`. Every level is brighter than
+//! the one below it. Low levels use the lowest current and step the duty by one,
+//! higher levels step the current up only once the duty runs out.
+
+use crate::LightingCurrent;
+
+/// Number of brightness levels.
+pub const LEVELS: usize = 256;
+
+/// Row current in mA and PWM duty (0 - 128) for each brightness level, gamma 2.2.
+///
+/// Output `current × duty` increases with every level, so no two levels look the same.
+pub const TABLE: [(u8, u8); LEVELS] = [
+    ( 5,   0), ( 5,   1), ( 5,   2), ( 5,   3), ( 5,   4), ( 5,   5), ( 5,   6), ( 5,   7),
+    ( 5,   8), ( 5,   9), ( 5,  10), ( 5,  11), ( 5,  12), ( 5,  13), ( 5,  14), ( 5,  15),
+    ( 5,  16), ( 5,  17), ( 5,  18), ( 5,  19), ( 5,  20), ( 5,  21), ( 5,  22), ( 5,  23),
+    ( 5,  24), ( 5,  25), ( 5,  26), ( 5,  27), ( 5,  28), ( 5,  29), ( 5,  30), ( 5,  31),
+    ( 5,  32), ( 5,  33), ( 5,  34), ( 5,  35), ( 5,  36), ( 5,  37), ( 5,  38), ( 5,  39),
+    ( 5,  40), ( 5,  41), ( 5,  42), ( 5,  43), ( 5,  44), ( 5,  45), ( 5,  46), ( 5,  47),
+    ( 5,  49), ( 5,  51), ( 5,  54), ( 5,  56), ( 5,  59), ( 5,  61), ( 5,  64), ( 5,  66),
+    ( 5,  69), ( 5,  72), ( 5,  74), ( 5,  77), ( 5,  80), ( 5,  83), ( 5,  86), ( 5,  89),
+    ( 5,  92), ( 5,  95), ( 5,  99), ( 5, 102), ( 5, 105), ( 5, 109), ( 5, 112), ( 5, 116),
+    ( 5, 119), ( 5, 123), ( 5, 127), (10,  66), (10,  67), (10,  69), (10,  71), (10,  73),
+    (10,  75), (10,  78), (10,  80), (10,  82), (10,  84), (10,  86), (10,  88), (10,  91),
+    (10,  93), (10,  95), (10,  98), (10, 100), (10, 102), (10, 105), (10, 107), (10, 110),
+    (10, 112), (10, 115), (10, 118), (10, 120), (10, 123), (10, 126), (10, 128), (15,  88),
+    (15,  89), (15,  91), (15,  93), (15,  95), (15,  97), (15,  99), (15, 101), (15, 103),
+    (15, 105), (15, 107), (15, 109), (15, 112), (15, 114), (15, 116), (15, 118), (15, 120),
+    (15, 122), (15, 125), (15, 127), (20,  97), (20,  99), (20, 101), (20, 102), (20, 104),
+    (20, 106), (20, 108), (20, 110), (20, 111), (20, 113), (20, 115), (20, 117), (20, 119),
+    (20, 121), (20, 123), (20, 125), (20, 127), (25, 103), (25, 105), (25, 106), (25, 108),
+    (25, 110), (25, 111), (25, 113), (25, 115), (25, 117), (25, 118), (25, 120), (25, 122),
+    (25, 124), (25, 125), (25, 127), (30, 108), (30, 109), (30, 111), (30, 112), (30, 114),
+    (30, 115), (30, 117), (30, 118), (30, 120), (30, 122), (30, 123), (30, 125), (30, 127),
+    (30, 128), (35, 111), (35, 113), (35, 114), (35, 116), (35, 117), (35, 119), (35, 120),
+    (35, 122), (35, 123), (35, 125), (35, 126), (35, 128), (40, 113), (40, 115), (40, 116),
+    (40, 118), (40, 119), (40, 120), (40, 122), (40, 123), (40, 125), (40, 126), (40, 128),
+    (45, 115), (45, 116), (45, 117), (45, 119), (45, 120), (45, 121), (45, 123), (45, 124),
+    (45, 126), (45, 127), (45, 128), (50, 117), (50, 118), (50, 119), (50, 121), (50, 122),
+    (50, 123), (50, 124), (50, 126), (50, 127), (50, 128), (55, 118), (55, 119), (55, 120),
+    (55, 122), (55, 123), (55, 124), (55, 125), (55, 127), (55, 128), (60, 118), (60, 120),
+    (60, 121), (60, 122), (60, 123), (60, 124), (60, 126), (60, 127), (60, 128), (65, 119),
+    (65, 120), (65, 122), (65, 123), (65, 124), (65, 125), (65, 126), (65, 127), (70, 119),
+    (70, 121), (70, 122), (70, 123), (70, 124), (70, 125), (70, 126), (70, 127), (70, 128),
+    (75, 121), (75, 122), (75, 123), (75, 124), (75, 125), (75, 126), (75, 127), (75, 128),
+];
+
+/// Perceptual brightness level (0 - 255).
+///
+/// ```
+/// use is31fl3730::Brightness;
+///
+/// let full = Brightness(255);
+/// assert_eq!(full.pwm(), 128);
+/// assert_eq!(full.current().milliamps(), 75);
+/// assert_eq!(Brightness(0).output(), 0);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Default)]
+pub struct Brightness(pub u8);
+
+impl Brightness {
+    /// Row current for this level.
+    pub fn current(&self) -> LightingCurrent {
+        LightingCurrent::from_milliamps(TABLE[self.0 as usize].0)
+            .unwrap_or(LightingCurrent::Current5mA)
+    }
+
+    /// PWM duty (0 - 128) for this level.
+    pub fn pwm(&self) -> u8 {
+        TABLE[self.0 as usize].1
+    }
+
+    /// Relative light output, `current × duty` (0 - 9600).
+    pub fn output(&self) -> u16 {
+        let (milliamps, pwm) = TABLE[self.0 as usize];
+        milliamps as u16 * pwm as u16
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn table_is_valid() {
+        for &(milliamps, pwm) in TABLE.iter() {
+            assert!(LightingCurrent::from_milliamps(milliamps).is_some());
+            assert!(pwm <= 128);
+        }
+        assert_eq!(TABLE[0].1, 0);
+        assert_eq!(TABLE[LEVELS - 1], (75, 128));
+    }
+
+    #[test]
+    fn output_is_strictly_increasing() {
+        for level in 1..=255u8 {
+            assert!(Brightness(level).output() > Brightness(level - 1).output());
+            assert!(Brightness(level).output() > 0);
+        }
+    }
+
+    #[test]
+    fn no_visible_steps() {
+        // Above the lowest levels, where a single duty step is already large,
+        // output should not jump by more than 10% between neighbouring levels.
+        for level in 33..=255u8 {
+            let previous = Brightness(level - 1).output() as u32;
+            let current = Brightness(level).output() as u32;
+            assert!(current * 10 <= previous * 11, "level {}", level);
+        }
+    }
+}
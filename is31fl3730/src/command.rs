@@ -0,0 +1,74 @@
+//! Register writes, shared by the blocking and async drivers.
+
+use crate::{Configuration, Lighting, DeviceError};
+use crate::register::Register;
+use crate::shadow::MATRIX_ROWS;
+
+/// Bytes of a single register write.
+pub type Command = [u8; 2];
+
+pub fn config(config: Configuration) -> Command {
+    [Register::Config as u8, config.byte()]
+}
+
+pub fn lighting(lighting: Lighting) -> Command {
+    [Register::LightingEffect as u8, lighting.byte()]
+}
+
+/// PWM register write, takes register value from `pwm_register`.
+pub fn pwm(value: u8) -> Command {
+    [Register::Pwm as u8, value]
+}
+
+pub fn update() -> Command {
+    [Register::UpdateColumn as u8, 0b00000000]
+}
+
+pub fn reset() -> Command {
+    [Register::Reset as u8, 0b00000000]
+}
+
+/// PWM register value for duty in 1/128 steps (0 - 128).
+pub fn pwm_register<E>(duty: u8) -> Result<u8, DeviceError<E>> {
+    match duty {
+        0..=127 => Ok(duty),
+        128 => Ok(0b1000_0000),
+        _ => Err(DeviceError::InvalidPwm(duty)),
+    }
+}
+
+/// Check that rows starting at `start_row` fit into the matrix data registers.
+pub fn check_rows<E>(start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+    if start_row as usize + rows.len() > MATRIX_ROWS {
+        return Err(DeviceError::InvalidRowRange {
+            start_row,
+            len: rows.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Auto-incremented write of matrix rows, starting at register `begin + start_row`.
+pub struct RowsCommand {
+    buffer: [u8; 1 + MATRIX_ROWS],
+    len: usize,
+}
+
+impl RowsCommand {
+    pub fn new(begin: Register, start_row: u8, rows: &[u8]) -> RowsCommand {
+        let mut buffer = [0; 1 + MATRIX_ROWS];
+        let len = rows.len() + 1;
+
+        buffer[0] = begin as u8 + start_row;
+        buffer[1..len].copy_from_slice(rows);
+
+        RowsCommand {
+            buffer,
+            len,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
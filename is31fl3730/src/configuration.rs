@@ -1,4 +1,11 @@
-#[derive(Copy, Clone)]
+use core::convert::TryFrom;
+use core::fmt;
+use crate::InvalidEncoding;
+
+/// Bits that must be zero.
+const RESERVED: u8 = 0b0110_0000;
+
+#[derive(Copy, Clone, PartialEq)]
 pub struct Configuration {
     configuration: u8,
 }
@@ -8,23 +15,56 @@ impl Configuration {
         self.configuration
     }
 
+    pub fn matrix_mode(&self) -> ConfigMatrixMode {
+        match self.configuration & ConfigMask::MatrixMode as u8 {
+            0b00 => ConfigMatrixMode::Size8x8,
+            0b01 => ConfigMatrixMode::Size7x9,
+            0b10 => ConfigMatrixMode::Size6x10,
+            _ => ConfigMatrixMode::Size5x11,
+        }
+    }
+
     pub fn set_matrix_mode(&mut self, matrix_mode: ConfigMatrixMode) -> &mut Self {
         self.set_bits(ConfigMask::MatrixMode as u8, matrix_mode as u8);
         self
     }
 
-    pub fn set_audio_input_enable(&mut self, value: bool) -> &mut Self {
-        self.set_bits(ConfigMask::Audio as u8, value as u8);
+    pub fn audio(&self) -> ConfigAudio {
+        match self.configuration & ConfigMask::Audio as u8 {
+            0 => ConfigAudio::LightingEffect,
+            _ => ConfigAudio::Signal,
+        }
+    }
+
+    pub fn set_audio(&mut self, audio: ConfigAudio) -> &mut Self {
+        self.set_bits(ConfigMask::Audio as u8, audio as u8);
         self
     }
 
+    pub fn set_audio_input_enable(&mut self, value: bool) -> &mut Self {
+        self.set_audio(if value { ConfigAudio::Signal } else { ConfigAudio::LightingEffect })
+    }
+
+    /// Display mode, the datasheet reads `1x` as both matrices.
+    pub fn display_mode(&self) -> ConfigDisplayMode {
+        match self.configuration & ConfigMask::DisplayMode as u8 {
+            0b0000_0000 => ConfigDisplayMode::Matrix1Only,
+            0b0000_1000 => ConfigDisplayMode::Matrix2Only,
+            _ => ConfigDisplayMode::Matrix1and2,
+        }
+    }
+
     pub fn set_display_mode(&mut self, display_mode: ConfigDisplayMode) -> &mut Self {
         self.set_bits(ConfigMask::DisplayMode as u8, display_mode as u8);
         self
     }
 
+    pub fn software_shutdown(&self) -> bool {
+        self.configuration & ConfigMask::SoftwareShutdown as u8 > 0
+    }
+
     pub fn set_software_shutdown(&mut self, value: bool) -> &mut Self {
-        self.set_bits(ConfigMask::SoftwareShutdown as u8, value as u8);
+        self.set_bits(ConfigMask::SoftwareShutdown as u8, if value { ConfigMask::SoftwareShutdown as u8 } else { 0 });
         self
     }
 
@@ -33,6 +73,39 @@ impl Configuration {
     }
 }
 
+impl TryFrom<u8> for Configuration {
+    type Error = InvalidEncoding;
+
+    /// Decode register byte, rejecting reserved bits.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value & RESERVED != 0 {
+            return Err(InvalidEncoding(value));
+        }
+        Ok(Configuration { configuration: value })
+    }
+}
+
+impl fmt::Debug for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Configuration")
+            .field("matrix_mode", &self.matrix_mode())
+            .field("display_mode", &self.display_mode())
+            .field("audio", &self.audio())
+            .field("software_shutdown", &self.software_shutdown())
+            .finish()
+    }
+}
+
+impl fmt::Display for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}, {}", self.matrix_mode(), self.display_mode(), self.audio())?;
+        if self.software_shutdown() {
+            write!(f, ", shutdown")?;
+        }
+        Ok(())
+    }
+}
+
 impl Default for Configuration {
     fn default() -> Self {
         Configuration {
@@ -54,7 +127,7 @@ pub enum ConfigMask {
     MatrixMode = 0b00000011,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum ConfigAudio {
     /// Matrix intensity is controlled  by  the  current setting in the Lighting Effect Register
@@ -63,7 +136,16 @@ pub enum ConfigAudio {
     Signal = 0b00000100,
 }
 
-#[derive(Copy, Clone)]
+impl fmt::Display for ConfigAudio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ConfigAudio::LightingEffect => "lighting effect",
+            ConfigAudio::Signal => "audio signal",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum ConfigDisplayMode {
     /// Matrix 1 only
@@ -74,11 +156,111 @@ pub enum ConfigDisplayMode {
     Matrix1and2 = 0b00011000,
 }
 
-#[derive(Copy, Clone)]
+impl fmt::Display for ConfigDisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ConfigDisplayMode::Matrix1Only => "matrix 1",
+            ConfigDisplayMode::Matrix2Only => "matrix 2",
+            ConfigDisplayMode::Matrix1and2 => "matrix 1 and 2",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum ConfigMatrixMode {
     Size8x8 = 0b00000000,
     Size7x9 = 0b00000001,
     Size6x10 = 0b00000010,
     Size5x11 = 0b00000011,
+}
+
+impl ConfigMatrixMode {
+    /// Pixels in a row.
+    pub fn width(&self) -> usize {
+        8 - *self as usize
+    }
+
+    /// Number of rows.
+    pub fn height(&self) -> usize {
+        8 + *self as usize
+    }
+}
+
+impl fmt::Display for ConfigMatrixMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width(), self.height())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::format;
+
+    #[test]
+    fn audio_input_enable_sets_audio_bit_only() {
+        let mut config = Configuration::default();
+        config.set_matrix_mode(ConfigMatrixMode::Size7x9);
+        config.set_audio_input_enable(true);
+        assert_eq!(config.byte(), 0b0000_0101);
+        assert!(config.audio() == ConfigAudio::Signal);
+        assert!(config.matrix_mode() == ConfigMatrixMode::Size7x9);
+
+        config.set_audio_input_enable(false);
+        assert_eq!(config.byte(), 0b0000_0001);
+        assert!(config.audio() == ConfigAudio::LightingEffect);
+    }
+
+    #[test]
+    fn matrix_mode_size() {
+        assert_eq!((ConfigMatrixMode::Size8x8.width(), ConfigMatrixMode::Size8x8.height()), (8, 8));
+        assert_eq!((ConfigMatrixMode::Size5x11.width(), ConfigMatrixMode::Size5x11.height()), (5, 11));
+    }
+
+    #[test]
+    fn software_shutdown_sets_shutdown_bit_only() {
+        let mut config = Configuration::default();
+        config.set_display_mode(ConfigDisplayMode::Matrix1and2);
+        config.set_software_shutdown(true);
+        assert_eq!(config.byte(), 0b1001_1000);
+        assert!(config.software_shutdown());
+
+        config.set_software_shutdown(false);
+        assert_eq!(config.byte(), 0b0001_1000);
+        assert!(!config.software_shutdown());
+    }
+
+    #[test]
+    fn decode_register() {
+        let config = Configuration::try_from(0b1001_1010).unwrap();
+        assert_eq!(config.matrix_mode(), ConfigMatrixMode::Size6x10);
+        assert_eq!(config.display_mode(), ConfigDisplayMode::Matrix1and2);
+        assert_eq!(config.audio(), ConfigAudio::LightingEffect);
+        assert!(config.software_shutdown());
+        assert_eq!(config.byte(), 0b1001_1010);
+
+        assert_eq!(Configuration::try_from(0b0010_0000), Err(InvalidEncoding(0b0010_0000)));
+
+        // Display mode `10` shows both matrices, like `11`.
+        let config = Configuration::try_from(0b0001_0000).unwrap();
+        assert_eq!(config.display_mode(), ConfigDisplayMode::Matrix1and2);
+        assert_eq!(config.byte(), 0b0001_0000);
+    }
+
+    #[test]
+    fn readable() {
+        let mut config = Configuration::default();
+        config.set_matrix_mode(ConfigMatrixMode::Size5x11).set_display_mode(ConfigDisplayMode::Matrix2Only);
+        assert_eq!(format!("{}", config), "5x11, matrix 2, lighting effect");
+        assert_eq!(
+            format!("{:?}", config),
+            "Configuration { matrix_mode: Size5x11, display_mode: Matrix2Only, audio: LightingEffect, software_shutdown: false }"
+        );
+
+        config.set_software_shutdown(true).set_audio(ConfigAudio::Signal);
+        assert_eq!(format!("{}", config), "5x11, matrix 2, audio signal, shutdown");
+    }
 }
\ No newline at end of file
@@ -1,30 +1,76 @@
-use crate::Device;
+use crate::{Device, ConfigMatrixMode, DeviceError};
 use crate::pixels::{DataBits};
-use hal;
+/// One of the two matrices driven by the chip.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MatrixBank {
+    Matrix1,
+    Matrix2,
+}
 
 pub trait OutputRows {
+    /// Pixels in a row.
     const WIDTH: usize;
+    /// Number of rows.
     const HEIGHT: usize;
+    /// Matrix mode the device must be configured with.
+    const MATRIX_MODE: ConfigMatrixMode;
+    /// Matrix the rows are written to.
+    const BANK: MatrixBank;
 
-    fn output_pixels<I2C, DATA>(&self, device: &mut Device<I2C>, data: &DATA) -> Result<(), I2C::Error>
+    fn output_pixels<I2C, DATA>(&self, device: &mut Device<I2C>, data: &DATA) -> Result<(), DeviceError<I2C::Error>>
         where
-            I2C: hal::blocking::i2c::Write,
+            I2C: hal::i2c::I2c,
             DATA: DataBits
     {
-        let mut buffer: [u8; 11] = [0; 11];
-
-        for row_index in 0..Self::HEIGHT {
-            for byte in data.row_bytes(row_index as i16, 0..1) {
-                buffer[row_index] = byte;
-            }
+        if device.config().matrix_mode() != Self::MATRIX_MODE {
+            return Err(DeviceError::MatrixModeMismatch);
         }
 
+        let mut buffer: [u8; 11] = [0; 11];
+        self.pack_rows(data, &mut buffer);
+
         self.write_buffer(device, &buffer[0..Self::HEIGHT])
     }
 
-    fn write_buffer<I2C>(&self, device: &mut Device<I2C>, buffer: &[u8]) -> Result<(), I2C::Error>
+    /// Pack the top-left `WIDTH` x `HEIGHT` pixels of data into row bytes.
+    ///
+    /// The leftmost pixel of a row goes to the highest bit used by the matrix mode,
+    /// e.g. bit 7 for 8 pixel rows and bit 4 for 5 pixel rows.
+    fn pack_rows<DATA>(&self, data: &DATA, buffer: &mut [u8; 11])
         where
-            I2C: hal::blocking::i2c::Write;
+            DATA: DataBits
+    {
+        for (row_index, row) in buffer.iter_mut().enumerate().take(Self::HEIGHT) {
+            *row = row_bits(data, row_index as i16, 0, Self::WIDTH);
+        }
+    }
+
+    /// Write packed rows to the matrix of this target.
+    fn write_buffer<I2C>(&self, device: &mut Device<I2C>, buffer: &[u8]) -> Result<(), DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c
+    {
+        match Self::BANK {
+            MatrixBank::Matrix1 => device.set_matrix1_rows(0, buffer),
+            MatrixBank::Matrix2 => device.set_matrix2_rows(0, buffer),
+        }
+    }
+}
+
+/// Read `width` (1 - 8) pixels of a row starting at pixel `x`, leftmost pixel in the highest bit.
+pub fn row_bits<DATA>(data: &DATA, row: i16, x: i16, width: usize) -> u8
+    where
+        DATA: DataBits
+{
+    let first_byte = x.div_euclid(8);
+    let skip = x.rem_euclid(8) as usize;
+    let byte_len = (skip + width).div_ceil(8);
+
+    let mut bits: u32 = 0;
+    for byte in data.row_bytes(row, first_byte..first_byte + byte_len as i16) {
+        bits = (bits << 8) | byte as u32;
+    }
+    ((bits >> (byte_len * 8 - skip - width)) & ((1 << width) - 1)) as u8
 }
 
 pub struct MatrixTargetPrimary8x8 {
@@ -34,13 +80,8 @@ pub struct MatrixTargetPrimary8x8 {
 impl OutputRows for MatrixTargetPrimary8x8 {
     const WIDTH: usize = 8;
     const HEIGHT: usize = 8;
-
-    fn write_buffer<I2C>(&self, device: &mut Device<I2C>, buffer: &[u8]) -> Result<(), I2C::Error>
-        where
-            I2C: hal::blocking::i2c::Write
-    {
-        device.set_matrix1_rows(0, buffer)
-    }
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size8x8;
+    const BANK: MatrixBank = MatrixBank::Matrix1;
 }
 
 pub struct MatrixTargetSecondary8x8 {
@@ -50,20 +91,160 @@ pub struct MatrixTargetSecondary8x8 {
 impl OutputRows for MatrixTargetSecondary8x8 {
     const WIDTH: usize = 8;
     const HEIGHT: usize = 8;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size8x8;
+    const BANK: MatrixBank = MatrixBank::Matrix2;
+}
 
-    fn write_buffer<I2C>(&self, device: &mut Device<I2C>, buffer: &[u8]) -> Result<(), I2C::Error>
-        where
-            I2C: hal::blocking::i2c::Write
-    {
-        device.set_matrix2_rows(0, buffer)
-    }
+pub struct MatrixTargetPrimary7x9 {
+
+}
+
+impl OutputRows for MatrixTargetPrimary7x9 {
+    const WIDTH: usize = 7;
+    const HEIGHT: usize = 9;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size7x9;
+    const BANK: MatrixBank = MatrixBank::Matrix1;
 }
 
-struct MatrixTargetPrimary7x9 {
+pub struct MatrixTargetSecondary7x9 {
+
+}
 
+impl OutputRows for MatrixTargetSecondary7x9 {
+    const WIDTH: usize = 7;
+    const HEIGHT: usize = 9;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size7x9;
+    const BANK: MatrixBank = MatrixBank::Matrix2;
 }
 
-struct MatrixTargetSecondary7x9 {
+pub struct MatrixTargetPrimary6x10 {
+
+}
 
+impl OutputRows for MatrixTargetPrimary6x10 {
+    const WIDTH: usize = 6;
+    const HEIGHT: usize = 10;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size6x10;
+    const BANK: MatrixBank = MatrixBank::Matrix1;
 }
 
+pub struct MatrixTargetSecondary6x10 {
+
+}
+
+impl OutputRows for MatrixTargetSecondary6x10 {
+    const WIDTH: usize = 6;
+    const HEIGHT: usize = 10;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size6x10;
+    const BANK: MatrixBank = MatrixBank::Matrix2;
+}
+
+pub struct MatrixTargetPrimary5x11 {
+
+}
+
+impl OutputRows for MatrixTargetPrimary5x11 {
+    const WIDTH: usize = 5;
+    const HEIGHT: usize = 11;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size5x11;
+    const BANK: MatrixBank = MatrixBank::Matrix1;
+}
+
+pub struct MatrixTargetSecondary5x11 {
+
+}
+
+impl OutputRows for MatrixTargetSecondary5x11 {
+    const WIDTH: usize = 5;
+    const HEIGHT: usize = 11;
+    const MATRIX_MODE: ConfigMatrixMode = ConfigMatrixMode::Size5x11;
+    const BANK: MatrixBank = MatrixBank::Matrix2;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigDisplayMode};
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W8, W16, H16};
+
+    fn device(matrix_mode: ConfigMatrixMode) -> Device<Chip> {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.modify_config(|c| c
+            .set_matrix_mode(matrix_mode)
+            .set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        device
+    }
+
+    #[test]
+    fn pack_5_pixel_rows() {
+        let mut canvas = BitCanvas::<W8, H16>::new(8, 11).unwrap();
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1000_0111]);
+        canvas.row_mut(10).unwrap().copy_from_slice(&[0b0000_1111]);
+
+        let mut device = device(ConfigMatrixMode::Size5x11);
+        MatrixTargetPrimary5x11 {}.output_pixels(&mut device, &canvas).unwrap();
+        MatrixTargetSecondary5x11 {}.output_pixels(&mut device, &canvas.offset_bytes(0, -10)).unwrap();
+        device.update().unwrap();
+
+        let m1 = device.i2c().visible_matrix1();
+        assert_eq!(m1.rows(), &[0b1_0000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0b0_0001]);
+        assert!(m1.pixel(0, 0) && m1.pixel(4, 10));
+
+        let m2 = device.i2c().visible_matrix2();
+        assert_eq!(m2.rows()[0], 0b0_0001);
+        assert_eq!(m2.lit_count(), 1);
+    }
+
+    #[test]
+    fn row_bits_at_pixel_offset() {
+        let mut canvas = BitCanvas::<W16, H16>::new(16, 1).unwrap();
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b0000_0111, 0b1100_0001]);
+
+        assert_eq!(row_bits(&canvas, 0, 0, 8), 0b0000_0111);
+        assert_eq!(row_bits(&canvas, 0, 5, 5), 0b1_1111);
+        assert_eq!(row_bits(&canvas, 0, 11, 5), 0b0_0001);
+        assert_eq!(row_bits(&canvas, 0, -2, 4), 0b00_00);
+        assert_eq!(row_bits(&canvas, 0, 14, 4), 0b01_00);
+    }
+
+    #[test]
+    fn pack_7_and_6_pixel_rows() {
+        let mut canvas = BitCanvas::<W16, H16>::new(16, 10).unwrap();
+        canvas.row_mut(8).unwrap().copy_from_slice(&[0b1111_1111, 0b1111_1111]);
+        canvas.row_mut(9).unwrap().copy_from_slice(&[0b1010_1010, 0]);
+
+        let mut device = device(ConfigMatrixMode::Size7x9);
+        MatrixTargetPrimary7x9 {}.output_pixels(&mut device, &canvas).unwrap();
+        device.update().unwrap();
+        assert_eq!(device.i2c().matrix1_data()[..10], [0, 0, 0, 0, 0, 0, 0, 0, 0b111_1111, 0]);
+
+        let mut device = self::device(ConfigMatrixMode::Size6x10);
+        MatrixTargetSecondary6x10 {}.output_pixels(&mut device, &canvas).unwrap();
+        device.update().unwrap();
+        assert_eq!(device.i2c().matrix2_data()[8..10], [0b11_1111, 0b10_1010]);
+        assert_eq!(device.i2c().visible_matrix2().lit_count(), 9);
+    }
+
+    #[test]
+    fn matrix_mode_must_match_target() {
+        let canvas = BitCanvas::<W8, H16>::new(8, 11).unwrap();
+
+        let mut device = device(ConfigMatrixMode::Size5x11);
+        assert_eq!(MatrixTargetPrimary8x8 {}.output_pixels(&mut device, &canvas), Err(DeviceError::MatrixModeMismatch));
+        assert_eq!(MatrixTargetSecondary7x9 {}.output_pixels(&mut device, &canvas), Err(DeviceError::MatrixModeMismatch));
+        assert_eq!(device.i2c().write_count(), 1);
+    }
+
+    #[test]
+    fn bus_error_kind() {
+        use hal::i2c::{Error as _, ErrorKind, NoAcknowledgeSource};
+
+        let canvas = BitCanvas::<W8, H16>::new(8, 11).unwrap();
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address01));
+        let error = MatrixTargetPrimary8x8 {}.output_pixels(&mut device, &canvas).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        assert_eq!(DeviceError::<crate::sim::Error>::MatrixModeMismatch.kind(), ErrorKind::Other);
+    }
+}
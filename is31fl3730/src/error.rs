@@ -0,0 +1,35 @@
+use hal::i2c::ErrorKind;
+
+/// Error returned by device operations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeviceError<E> {
+    /// I2C bus error.
+    Bus(E),
+    /// Rows do not fit into the 11 matrix data registers.
+    InvalidRowRange {
+        start_row: u8,
+        len: usize,
+    },
+    /// PWM duty above 128.
+    InvalidPwm(u8),
+    /// Device is configured for a different matrix mode than the target.
+    MatrixModeMismatch,
+    /// Device is backing off after repeated bus errors and was not used.
+    Unavailable,
+}
+
+/// Register byte uses a reserved encoding.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InvalidEncoding(pub u8);
+
+impl<E> hal::i2c::Error for DeviceError<E>
+    where
+        E: hal::i2c::Error
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DeviceError::Bus(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
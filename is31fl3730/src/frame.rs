@@ -0,0 +1,155 @@
+use crate::ConfigMatrixMode;
+use crate::display::{row_bits, MatrixBank, OutputRows};
+use crate::pixels::DataBits;
+use crate::shadow::MATRIX_ROWS;
+
+/// Contents of both matrices, prepared off-device and shown with `Device::present`.
+///
+/// Rows use the same bit layout as the matrix data registers.
+///
+/// The example needs the simulator of the `std` feature.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use is31fl3730::{Address, ConfigDisplayMode, ConfigMatrixMode, Device, Frame};
+/// use is31fl3730::sim::Chip;
+/// use bitcanvas::BitCanvas;
+/// use bitcanvas::consts::{W16, H8};
+///
+/// let mut canvas = BitCanvas::<W16, H8>::new(16, 8).unwrap();
+/// canvas.row_mut(0).unwrap().copy_from_slice(&[0b1000_0000, 0b0000_0001]);
+///
+/// let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+/// device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+/// device.present(&Frame::from_data(ConfigMatrixMode::Size8x8, &canvas)).unwrap();
+/// assert!(device.i2c().visible_matrix1().pixel(0, 0));
+/// assert!(device.i2c().visible_matrix2().pixel(7, 0));
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    matrix1: [u8; MATRIX_ROWS],
+    matrix2: [u8; MATRIX_ROWS],
+}
+
+impl Frame {
+    pub fn new() -> Frame {
+        Frame::default()
+    }
+
+    /// Frame of packed rows, usable in statics.
+    pub const fn from_rows(matrix1: [u8; MATRIX_ROWS], matrix2: [u8; MATRIX_ROWS]) -> Frame {
+        Frame {
+            matrix1,
+            matrix2,
+        }
+    }
+
+    /// Build frame from data, first matrix showing the left half and second matrix the right half.
+    pub fn from_data<DATA>(matrix_mode: ConfigMatrixMode, data: &DATA) -> Frame
+        where
+            DATA: DataBits
+    {
+        Frame::from_data_at(matrix_mode, data, 0)
+    }
+
+    /// Build frame from two matrices of data, starting at pixel `x`.
+    pub fn from_data_at<DATA>(matrix_mode: ConfigMatrixMode, data: &DATA, x: i16) -> Frame
+        where
+            DATA: DataBits
+    {
+        let width = matrix_mode.width();
+        let mut frame = Frame::new();
+
+        for y in 0..matrix_mode.height() {
+            frame.matrix1[y] = row_bits(data, y as i16, x, width);
+            frame.matrix2[y] = row_bits(data, y as i16, x + width as i16, width);
+        }
+
+        frame
+    }
+
+    /// Pack data into the matrix of target, the same way `OutputRows::output_pixels` does.
+    pub fn draw<T, DATA>(&mut self, target: &T, data: &DATA) -> &mut Self
+        where
+            T: OutputRows,
+            DATA: DataBits
+    {
+        let mut buffer: [u8; MATRIX_ROWS] = [0; MATRIX_ROWS];
+        target.pack_rows(data, &mut buffer);
+
+        let rows = match T::BANK {
+            MatrixBank::Matrix1 => &mut self.matrix1,
+            MatrixBank::Matrix2 => &mut self.matrix2,
+        };
+        rows[..T::HEIGHT].copy_from_slice(&buffer[..T::HEIGHT]);
+        self
+    }
+
+    pub fn matrix1(&self) -> &[u8; MATRIX_ROWS] {
+        &self.matrix1
+    }
+
+    pub fn matrix2(&self) -> &[u8; MATRIX_ROWS] {
+        &self.matrix2
+    }
+
+    pub fn matrix1_mut(&mut self) -> &mut [u8; MATRIX_ROWS] {
+        &mut self.matrix1
+    }
+
+    pub fn matrix2_mut(&mut self) -> &mut [u8; MATRIX_ROWS] {
+        &mut self.matrix2
+    }
+
+    /// Clear all pixels.
+    pub fn clear(&mut self) {
+        *self = Frame::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigDisplayMode, Device};
+    use crate::display::{MatrixTargetPrimary5x11, MatrixTargetSecondary5x11};
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W8, H16};
+
+    #[test]
+    fn draw_into_banks() {
+        let mut canvas = BitCanvas::<W8, H16>::new(8, 11).unwrap();
+        canvas.row_mut(10).unwrap().copy_from_slice(&[0b1000_1000]);
+
+        let mut frame = Frame::new();
+        frame
+            .draw(&MatrixTargetPrimary5x11 {}, &canvas)
+            .draw(&MatrixTargetSecondary5x11 {}, &canvas.offset_bytes(0, -10));
+        assert_eq!(frame.matrix1()[10], 0b1_0001);
+        assert_eq!(frame.matrix2()[0], 0b1_0001);
+        assert_eq!(frame.matrix2()[1..], [0; 10]);
+    }
+
+    #[test]
+    fn present_latches_once_and_sends_only_changes() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        let mut frame = Frame::new();
+        frame.matrix1_mut()[0] = 0xff;
+        frame.matrix2_mut()[7] = 0x81;
+
+        device.present(&frame).unwrap();
+        assert_eq!(device.i2c().write_count(), 4);
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 8);
+        assert_eq!(device.i2c().visible_matrix2().lit_count(), 2);
+
+        device.present(&frame).unwrap();
+        assert_eq!(device.i2c().write_count(), 4);
+
+        frame.matrix2_mut()[7] = 0;
+        device.present(&frame).unwrap();
+        assert_eq!(device.i2c().write_count(), 6);
+        assert_eq!(device.i2c().visible_matrix2().lit_count(), 0);
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 8);
+    }
+}
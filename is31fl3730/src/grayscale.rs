@@ -0,0 +1,251 @@
+//! Software grayscale with bit-angle modulation.
+//!
+//! The chip stores one bit per LED, so a pixel with several brightness levels is shown as
+//! a sequence of binary frames, one per bit of the level. Every frame is shown for one tick,
+//! and the weight of its bit comes from the global PWM register: the most significant bit
+//! is shown at full duty, the next one at half duty, and so on.
+//!
+//! A pixel at the highest level is lit for the sum of the plane duties, 256 - 2^(8 - bits),
+//! out of 128 · bits, so peak brightness is capped at about 255 / (128 · bits) of full PWM,
+//! a quarter at 8 bits.
+//!
+//! Call `Grayscale::tick` at a fixed rate, for example from a timer. A full grayscale frame
+//! takes `bits` ticks, so the tick rate must be `bits` times the wanted refresh rate, and the bus
+//! must be fast enough to send a binary frame every tick, see `Grayscale::bandwidth`.
+
+use crate::{ConfigMatrixMode, Device, DeviceError, Frame};
+use crate::pixels::{LitPixels, PixelBits};
+
+/// Maximum bits per pixel. The least significant bit is shown at the lowest PWM duty, 1/128.
+pub const MAX_BITS: u8 = 8;
+
+/// Address and register bytes that start every bus transaction.
+const HEADER_BYTES: u32 = 2;
+/// Data bytes of the update and PWM writes.
+const UPDATE_BYTES: u32 = 1;
+
+/// Pixels with several brightness levels.
+pub trait GrayPixels {
+    /// Level of the pixel, bits above the renderer depth are ignored.
+    fn level(&self, x: i16, y: i16) -> u8;
+}
+
+impl<F> GrayPixels for F where F: Fn(i16, i16) -> u8 {
+    fn level(&self, x: i16, y: i16) -> u8 {
+        self(x, y)
+    }
+}
+
+/// Renders grayscale pixels to both matrices of a device, one bit plane per tick.
+///
+/// The first matrix shows the left half of the pixels, the second matrix the right half.
+#[derive(Copy, Clone)]
+pub struct Grayscale {
+    matrix_mode: ConfigMatrixMode,
+    bits: u8,
+    plane: u8,
+}
+
+impl Grayscale {
+    /// Renderer with `bits` (1 - `MAX_BITS`) per pixel.
+    pub fn new(matrix_mode: ConfigMatrixMode, bits: u8) -> Option<Grayscale> {
+        if bits == 0 || bits > MAX_BITS {
+            return None;
+        }
+
+        Some(Grayscale {
+            matrix_mode,
+            bits,
+            plane: 0,
+        })
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Number of brightness levels.
+    pub fn levels(&self) -> u16 {
+        1 << self.bits
+    }
+
+    /// Bit plane shown by the next tick.
+    pub fn plane(&self) -> u8 {
+        self.plane
+    }
+
+    /// PWM duty (1 - 128) the bit plane is shown with.
+    pub fn plane_pwm(&self, plane: u8) -> u8 {
+        (128u16 >> (self.bits - 1 - plane)) as u8
+    }
+
+    /// Show the next bit plane of pixels.
+    ///
+    /// Rows that did not change since the previous plane are not sent. The PWM register is
+    /// written first and the plane is latched last.
+    ///
+    /// The device must be configured with the matrix mode of the renderer.
+    pub fn tick<I2C, S>(&mut self, device: &mut Device<I2C>, pixels: &S) -> Result<(), DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c,
+            S: GrayPixels
+    {
+        if device.config().matrix_mode() != self.matrix_mode {
+            return Err(DeviceError::MatrixModeMismatch);
+        }
+
+        let pwm = self.plane_pwm(self.plane);
+        if device.requested_output().1 != pwm {
+            device.set_pwm(pwm)?;
+        }
+
+        let plane = PixelBits::new(BitPlane::new(pixels, self.plane), self.matrix_mode.width() as i16 * 2);
+        device.present(&Frame::from_data(self.matrix_mode, &plane))?;
+
+        self.plane = (self.plane + 1) % self.bits;
+        Ok(())
+    }
+
+    /// Worst case bus bits sent by a tick: both matrices, update and PWM.
+    ///
+    /// Counts 9 bits per byte and 2 bits for start and stop conditions of every transaction.
+    /// A current limit set on the device adds lighting and PWM writes, which are not counted.
+    pub fn tick_bits(&self) -> u32 {
+        let rows = HEADER_BYTES + self.matrix_mode.height() as u32;
+        let command = HEADER_BYTES + UPDATE_BYTES;
+        let bytes = rows * 2 + command * 2;
+        bytes * 9 + 4 * 2
+    }
+
+    /// Minimum bus speed, in bits per second, for the grayscale refresh rate in Hz.
+    pub fn bandwidth(&self, refresh_hz: u32) -> u32 {
+        refresh_hz * self.bits as u32 * self.tick_bits()
+    }
+
+    /// Maximum tick rate, in Hz, at bus speed in bits per second.
+    pub fn max_tick_rate(&self, bus_hz: u32) -> u32 {
+        bus_hz / self.tick_bits()
+    }
+
+    /// Maximum grayscale refresh rate, in Hz, at bus speed in bits per second.
+    pub fn max_refresh_rate(&self, bus_hz: u32) -> u32 {
+        self.max_tick_rate(bus_hz) / self.bits as u32
+    }
+}
+
+/// Pixels that have a bit of their level set.
+pub struct BitPlane<'a, S> where S: GrayPixels {
+    pixels: &'a S,
+    bit: u8,
+}
+
+impl<'a, S> BitPlane<'a, S> where S: GrayPixels {
+    pub fn new(pixels: &'a S, bit: u8) -> BitPlane<'a, S> {
+        BitPlane {
+            pixels,
+            bit,
+        }
+    }
+}
+
+impl<'a, S> LitPixels for BitPlane<'a, S> where S: GrayPixels {
+    fn lit(&self, x: i16, y: i16) -> bool {
+        self.pixels.level(x, y) & (1 << self.bit) != 0
+    }
+}
+
+impl<'a, S> Clone for BitPlane<'a, S> where S: GrayPixels {
+    fn clone(&self) -> Self {
+        BitPlane::new(self.pixels, self.bit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigDisplayMode};
+    use crate::sim::Chip;
+    use crate::transcript::Recorder;
+
+    #[test]
+    fn depth_is_limited() {
+        assert!(Grayscale::new(ConfigMatrixMode::Size8x8, 0).is_none());
+        assert!(Grayscale::new(ConfigMatrixMode::Size8x8, MAX_BITS + 1).is_none());
+
+        let grayscale = Grayscale::new(ConfigMatrixMode::Size8x8, MAX_BITS).unwrap();
+        assert_eq!(grayscale.levels(), 256);
+        assert_eq!(grayscale.plane_pwm(0), 1);
+        assert_eq!(grayscale.plane_pwm(7), 128);
+    }
+
+    #[test]
+    fn planes_are_weighted_by_pwm() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        let mut grayscale = Grayscale::new(ConfigMatrixMode::Size8x8, 3).unwrap();
+        // Level 5 on the first matrix, level 2 on the second.
+        let pixels = |x, y| match (x, y) {
+            (0, 0) => 5,
+            (8, 0) => 2,
+            _ => 0,
+        };
+
+        let mut on_time = [0u32; 2];
+        for _ in 0..3 {
+            grayscale.tick(&mut device, &pixels).unwrap();
+            let chip = device.i2c();
+            if chip.visible_matrix1().pixel(0, 0) {
+                on_time[0] += device.pwm() as u32;
+            }
+            if chip.visible_matrix2().pixel(0, 0) {
+                on_time[1] += device.pwm() as u32;
+            }
+        }
+        assert_eq!(grayscale.plane(), 0);
+        assert_eq!(on_time, [5 * 32, 2 * 32]);
+    }
+
+    #[test]
+    fn unchanged_planes_send_only_pwm() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        let mut grayscale = Grayscale::new(ConfigMatrixMode::Size8x8, 2).unwrap();
+        let pixels = |_, _| 3;
+
+        grayscale.tick(&mut device, &pixels).unwrap();
+        let writes = device.i2c().write_count();
+        grayscale.tick(&mut device, &pixels).unwrap();
+        assert_eq!(device.i2c().write_count(), writes + 1);
+    }
+
+    #[test]
+    fn pwm_is_written_before_latch() {
+        let mut device = Device::new(Address::Address00, Recorder::new(Chip::new(Address::Address00)));
+        let mut grayscale = Grayscale::new(ConfigMatrixMode::Size8x8, 2).unwrap();
+        let pixels = |x, _| x as u8 & 0b11;
+
+        grayscale.tick(&mut device, &pixels).unwrap();
+        let transactions = device.i2c().transcript().transactions();
+        assert_eq!(transactions.first().unwrap().bytes, [0x19, 64]);
+        assert_eq!(transactions.last().unwrap().bytes, [0x0c, 0]);
+    }
+
+    #[test]
+    fn matrix_mode_must_match() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        let mut grayscale = Grayscale::new(ConfigMatrixMode::Size5x11, 2).unwrap();
+
+        assert_eq!(grayscale.tick(&mut device, &|_, _| 3), Err(DeviceError::MatrixModeMismatch));
+        assert_eq!(grayscale.plane(), 0);
+        assert_eq!(device.i2c().write_count(), 0);
+    }
+
+    #[test]
+    fn bandwidth() {
+        let grayscale = Grayscale::new(ConfigMatrixMode::Size8x8, 4).unwrap();
+        // Two 10 byte row writes, update and PWM: 26 bytes and 4 transactions.
+        assert_eq!(grayscale.tick_bits(), 242);
+        assert_eq!(grayscale.bandwidth(100), 96_800);
+        assert_eq!(grayscale.max_tick_rate(400_000), 1652);
+        assert_eq!(grayscale.max_refresh_rate(400_000), 413);
+    }
+}
@@ -0,0 +1,290 @@
+//! Physical placement of matrices on a canvas.
+//!
+//! Instead of chaining `DataBits` transforms by hand for every matrix, describe where each
+//! matrix is mounted and let the layout derive the chain. Quarter turns use `rotate_90`,
+//! mirroring and half turns are applied to the packed rows:
+//!
+//! ```
+//! use is31fl3730::{Address, ConfigMatrixMode};
+//! use is31fl3730::display::MatrixBank;
+//! use is31fl3730::layout::{Layout, Placement, Rotation};
+//!
+//! const SCREEN: Layout = Layout {
+//!     matrix_mode: ConfigMatrixMode::Size8x8,
+//!     placements: &[
+//!         Placement { address: Address::Address00, bank: MatrixBank::Matrix1, x: 0, y: 0, rotation: Rotation::Deg0, mirror: true },
+//!         Placement { address: Address::Address00, bank: MatrixBank::Matrix2, x: 8, y: 0, rotation: Rotation::Deg270, mirror: false },
+//!     ],
+//! };
+//!
+//! assert_eq!(SCREEN.size(), (16, 8));
+//! ```
+
+use crate::{Address, ConfigMatrixMode, Device, DeviceError, Frame};
+use crate::display::{
+    MatrixBank,
+    MatrixTargetPrimary5x11,
+    MatrixTargetPrimary6x10,
+    MatrixTargetPrimary7x9,
+    MatrixTargetPrimary8x8,
+    OutputRows,
+};
+use crate::pixels::DataBits;
+
+/// Clockwise rotation of a matrix as mounted, relative to the canvas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+/// Where one matrix is mounted.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Placement {
+    /// Chip driving the matrix.
+    pub address: Address,
+    /// Matrix of the chip.
+    pub bank: MatrixBank,
+    /// Canvas column of the top-left corner of the mounted matrix.
+    pub x: i16,
+    /// Canvas row of the top-left corner of the mounted matrix.
+    pub y: i16,
+    pub rotation: Rotation,
+    /// Matrix columns are wired right to left.
+    pub mirror: bool,
+}
+
+impl Placement {
+    /// Width and height the matrix covers on the canvas.
+    fn size(&self, matrix_mode: ConfigMatrixMode) -> (i16, i16) {
+        let (width, height) = (matrix_mode.width() as i16, matrix_mode.height() as i16);
+        match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (width, height),
+            Rotation::Deg90 | Rotation::Deg270 => (height, width),
+        }
+    }
+}
+
+/// All matrices of a display, sharing the same matrix mode.
+#[derive(Copy, Clone)]
+pub struct Layout<'a> {
+    pub matrix_mode: ConfigMatrixMode,
+    pub placements: &'a [Placement],
+}
+
+impl<'a> Layout<'a> {
+    /// Width and height of the canvas covered by all matrices.
+    pub fn size(&self) -> (i16, i16) {
+        self.placements.iter().fold((0, 0), |(width, height), p| {
+            let (w, h) = p.size(self.matrix_mode);
+            (width.max(p.x + w), height.max(p.y + h))
+        })
+    }
+
+    /// Rows of data as seen by the matrix at placement, packed like `OutputRows::pack_rows`.
+    pub fn rows<DATA>(&self, placement: &Placement, data: &DATA) -> [u8; 11]
+        where
+            DATA: DataBits
+    {
+        let (width, height) = (self.matrix_mode.width(), self.matrix_mode.height());
+        let mut rows = [0; 11];
+
+        // Quarter turns read canvas columns as rows, the first row is column x.
+        match placement.rotation {
+            Rotation::Deg0 | Rotation::Deg180 =>
+                pack_rows(self.matrix_mode, &data.offset_bits(-placement.x, -placement.y), &mut rows),
+            Rotation::Deg90 | Rotation::Deg270 => {
+                let turned = data.offset_bytes(0, 8 - width as i16 - placement.y);
+                let turned = turned.rotate_90();
+                pack_rows(self.matrix_mode, &turned.offset_bytes(0, -placement.x), &mut rows);
+            },
+        }
+
+        // Turning by half mirrors both columns and rows.
+        let half_turn = placement.rotation == Rotation::Deg90 || placement.rotation == Rotation::Deg180;
+        if placement.mirror != half_turn {
+            for row in rows.iter_mut().take(height) {
+                *row = row.reverse_bits() >> (8 - width);
+            }
+        }
+        if half_turn {
+            rows[..height].reverse();
+        }
+
+        rows
+    }
+
+    /// Contents of both matrices of the chip at address. Matrices not in the layout stay blank.
+    pub fn frame<DATA>(&self, address: Address, data: &DATA) -> Frame
+        where
+            DATA: DataBits
+    {
+        let mut frame = Frame::new();
+
+        for placement in self.placements.iter().filter(|p| p.address == address) {
+            let rows = self.rows(placement, data);
+            match placement.bank {
+                MatrixBank::Matrix1 => *frame.matrix1_mut() = rows,
+                MatrixBank::Matrix2 => *frame.matrix2_mut() = rows,
+            }
+        }
+
+        frame
+    }
+
+    /// Show data on the matrices driven by device.
+    pub fn output_pixels<I2C, DATA>(&self, device: &mut Device<I2C>, data: &DATA) -> Result<(), DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c,
+            DATA: DataBits
+    {
+        if device.config().matrix_mode() != self.matrix_mode {
+            return Err(DeviceError::MatrixModeMismatch);
+        }
+
+        let frame = self.frame(device.address(), data);
+        device.present(&frame)
+    }
+}
+
+/// Pack rows with the target of the matrix mode.
+fn pack_rows<DATA>(matrix_mode: ConfigMatrixMode, data: &DATA, rows: &mut [u8; 11])
+    where
+        DATA: DataBits
+{
+    match matrix_mode {
+        ConfigMatrixMode::Size8x8 => MatrixTargetPrimary8x8 {}.pack_rows(data, rows),
+        ConfigMatrixMode::Size7x9 => MatrixTargetPrimary7x9 {}.pack_rows(data, rows),
+        ConfigMatrixMode::Size6x10 => MatrixTargetPrimary6x10 {}.pack_rows(data, rows),
+        ConfigMatrixMode::Size5x11 => MatrixTargetPrimary5x11 {}.pack_rows(data, rows),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ConfigDisplayMode;
+    use crate::display::{row_bits, MatrixTargetSecondary8x8};
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W32, H8};
+
+    fn placement(bank: MatrixBank, x: i16, rotation: Rotation, mirror: bool) -> Placement {
+        Placement { address: Address::Address00, bank, x, y: 0, rotation, mirror }
+    }
+
+    fn canvas() -> BitCanvas<W32, H8> {
+        let mut canvas = BitCanvas::<W32, H8>::new(32, 8).unwrap();
+        for y in 0..8 {
+            let row = canvas.row_mut(y).unwrap();
+            row.copy_from_slice(&[0b1100_0000 >> y, 0b1010_0110 ^ y as u8, 0x81 | (1 << y), 0xf0 >> y]);
+        }
+        canvas
+    }
+
+    fn packed<DATA>(data: &DATA) -> [u8; 11]
+        where
+            DATA: DataBits
+    {
+        let mut rows = [0; 11];
+        MatrixTargetPrimary8x8 {}.pack_rows(data, &mut rows);
+        rows
+    }
+
+    #[test]
+    fn matches_transform_chains() {
+        let canvas = canvas();
+        let layout = Layout {
+            matrix_mode: ConfigMatrixMode::Size8x8,
+            placements: &[],
+        };
+
+        // Mounted as on the board: x 0 and 16 mirrored, x 8 and 24 turned.
+        for &byte in [0, 2].iter() {
+            let mirrored = placement(MatrixBank::Matrix1, byte * 8, Rotation::Deg0, true);
+            let rotated = placement(MatrixBank::Matrix2, byte * 8 + 8, Rotation::Deg270, false);
+
+            let mirrored_chain = canvas.flip_h();
+            let mirrored_chain = mirrored_chain.offset_bytes(byte + 1, 0);
+            let rotated_chain = canvas.offset_bytes(-(byte + 1), 0);
+            let rotated_chain = rotated_chain.rotate_90();
+
+            assert_eq!(layout.rows(&mirrored, &canvas), packed(&mirrored_chain));
+            assert_eq!(layout.rows(&rotated, &canvas), packed(&rotated_chain));
+        }
+    }
+
+    #[test]
+    fn rotations_place_corner() {
+        let mut canvas = BitCanvas::<W32, H8>::new(32, 8).unwrap();
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1000_0000, 0, 0, 0]);
+        let layout = Layout {
+            matrix_mode: ConfigMatrixMode::Size7x9,
+            placements: &[],
+        };
+        let rows = |rotation, mirror| layout.rows(&placement(MatrixBank::Matrix1, 0, rotation, mirror), &canvas);
+
+        assert_eq!(rows(Rotation::Deg0, false)[0], 0b100_0000);
+        assert_eq!(rows(Rotation::Deg90, false)[8], 0b100_0000);
+        assert_eq!(rows(Rotation::Deg180, false)[8], 0b000_0001);
+        assert_eq!(rows(Rotation::Deg270, false)[0], 0b000_0001);
+        assert_eq!(rows(Rotation::Deg0, true)[0], 0b000_0001);
+        assert_eq!(rows(Rotation::Deg90, true)[8], 0b000_0001);
+        assert_eq!(rows(Rotation::Deg180, true)[8], 0b100_0000);
+        assert_eq!(rows(Rotation::Deg270, true)[0], 0b100_0000);
+        assert_eq!(rows(Rotation::Deg0, false)[..9].iter().map(|row| row.count_ones()).sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn placements_between_bytes() {
+        let mut canvas = BitCanvas::<W32, H8>::new(32, 8).unwrap();
+        canvas.row_mut(1).unwrap().copy_from_slice(&[0b0000_0110, 0b0100_0000, 0, 0]);
+        let layout = Layout {
+            matrix_mode: ConfigMatrixMode::Size5x11,
+            placements: &[],
+        };
+
+        for x in 0..5 {
+            let placed = layout.rows(&placement(MatrixBank::Matrix1, 5, Rotation::Deg0, false), &canvas);
+            assert_eq!(placed[1] >> (4 - x) & 1 != 0, row_bits(&canvas, 1, 5 + x, 1) != 0);
+        }
+
+        // Turned, matrix row r shows canvas column x + r, with canvas row 0 on the right.
+        let turned = layout.rows(&placement(MatrixBank::Matrix1, 5, Rotation::Deg270, false), &canvas);
+        assert_eq!(turned[0], 0b0_0010);
+        assert_eq!(turned[1], 0b0_0010);
+        assert_eq!(turned[4], 0b0_0010);
+        assert_eq!(turned[..11].iter().map(|row| row.count_ones()).sum::<u32>(), 3);
+    }
+
+    #[test]
+    fn output_to_addressed_chip() {
+        let canvas = canvas();
+        let layout = Layout {
+            matrix_mode: ConfigMatrixMode::Size8x8,
+            placements: &[
+                Placement { address: Address::Address11, bank: MatrixBank::Matrix1, x: 0, y: 0, rotation: Rotation::Deg0, mirror: true },
+                Placement { address: Address::Address11, bank: MatrixBank::Matrix2, x: 8, y: 0, rotation: Rotation::Deg270, mirror: false },
+                Placement { address: Address::Address01, bank: MatrixBank::Matrix1, x: 16, y: 0, rotation: Rotation::Deg0, mirror: true },
+                Placement { address: Address::Address01, bank: MatrixBank::Matrix2, x: 24, y: 0, rotation: Rotation::Deg270, mirror: false },
+            ],
+        };
+        assert_eq!(layout.size(), (32, 8));
+
+        let mut expected = Device::new(Address::Address01, Chip::new(Address::Address01));
+        MatrixTargetPrimary8x8 {}.output_pixels(&mut expected, &canvas.flip_h().offset_bytes(3, 0)).unwrap();
+        MatrixTargetSecondary8x8 {}.output_pixels(&mut expected, &canvas.offset_bytes(-3, 0).rotate_90()).unwrap();
+
+        let mut device = Device::new(Address::Address01, Chip::new(Address::Address01));
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        layout.output_pixels(&mut device, &canvas).unwrap();
+        assert_eq!(device.matrix1_rows(), expected.matrix1_rows());
+        assert_eq!(device.matrix2_rows(), expected.matrix2_rows());
+        assert!(device.i2c().visible_matrix2().lit_count() > 0);
+
+        device.modify_config(|c| c.set_matrix_mode(ConfigMatrixMode::Size5x11)).unwrap();
+        assert_eq!(layout.output_pixels(&mut device, &canvas), Err(DeviceError::MatrixModeMismatch));
+    }
+}
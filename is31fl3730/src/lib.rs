@@ -8,8 +8,35 @@ mod address;
 mod register;
 mod lighting;
 mod configuration;
+mod audio;
+mod sleep;
+mod resilient;
+mod shadow;
+mod command;
+mod error;
+mod frame;
 pub mod display;
 pub mod pixels;
+pub mod typestate;
+pub mod animation;
+pub mod brightness;
+pub mod array;
+pub mod batch;
+pub mod layout;
+pub mod microdot;
+pub mod grayscale;
+pub mod testpattern;
+pub mod power;
+pub mod scan;
+pub mod sequence;
+#[cfg(any(test, feature = "std"))]
+pub mod sim;
+#[cfg(any(test, feature = "std"))]
+pub mod transcript;
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "eh02")]
+pub use i2c_compat as eh02;
 
 pub use lighting::{
     Lighting,
@@ -23,94 +50,797 @@ pub use configuration::{
     ConfigAudio,
 };
 pub use address::{Address};
+pub use audio::{AudioSettings};
+pub use brightness::{Brightness};
+pub use error::{DeviceError, InvalidEncoding};
+pub use frame::{Frame};
+pub use power::{CurrentLimit};
+pub use resilient::{
+    Resilient,
+    Recipe,
+    RetryPolicy,
+    Stats,
+};
+pub use sleep::{
+    ShutdownGuard,
+    IdleBlank,
+    IdleAction,
+};
+
+use shadow::{Shadow, Dirty};
 
 pub struct Device<I2C>
     where
-        I2C: hal::blocking::i2c::Write,
+        I2C: hal::i2c::I2c,
 {
     address: Address,
     i2c: I2C,
-    lighting: Lighting,
-    config: Configuration,
+    shadow: Shadow,
+    limiter: Option<power::Limiter>,
 }
 
 impl<I2C, E> Device<I2C>
     where
-        I2C: hal::blocking::i2c::Write<Error = E>,
+        I2C: hal::i2c::I2c<Error = E>,
 {
     pub fn new(address: Address, i2c: I2C) -> Device<I2C> {
         Device {
             address,
             i2c,
-            lighting: Lighting::default(),
-            config: Configuration::default(),
+            shadow: Shadow::default(),
+            limiter: None,
         }
     }
 
-    /// Set PWM value (0b0000000 - 0b1111111)
-    pub fn set_pwm(&mut self, value: u8) -> Result<(), E> {
-        self.i2c.write(self.address as u8, &[register::Register::Pwm as u8, (value & 0b0111_1111) | 0b1000_0000])
+    /// Address the device was created with.
+    pub fn address(&self) -> Address {
+        self.address
     }
 
-    /// Write pixels for the first matrix. Call update to flush updates.
-    pub fn set_matrix1_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), E> {
-        const BUFLEN: usize = 1 + 11;
-        let mut writebuf: [u8; BUFLEN] = [0; BUFLEN];
-
-        let final_write_len = rows.len() + 1;
-
-        writebuf[0] = register::Register::Matrix1Begin as u8 + start_row;
-        writebuf[1..final_write_len].copy_from_slice(rows);
+    /// Get the underlying bus.
+    pub fn i2c(&self) -> &I2C {
+        &self.i2c
+    }
 
-        self.i2c.write(self.address as u8, &writebuf[..final_write_len])
+    /// Release the underlying bus.
+    pub fn release(self) -> I2C {
+        self.i2c
     }
 
-    /// Write pixels for the second matrix. Call update to flush updates.
-    pub fn set_matrix2_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), E> {
-        const BUFLEN: usize = 1 + 11;
-        let mut writebuf: [u8; BUFLEN] = [0; BUFLEN];
+    /// Set PWM duty in 1/128 steps (0 - 128), 128 is full brightness.
+    ///
+    /// With a current limit, the duty is remembered as requested and lowered to fit the
+    /// shown frame.
+    pub fn set_pwm(&mut self, value: u8) -> Result<(), DeviceError<E>> {
+        let register = command::pwm_register(value)?;
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.pwm = value;
+        }
 
-        let final_write_len = rows.len() + 1;
+        if let Some((current, pwm)) = self.limited_output(&self.shadow_frame()) {
+            return self.write_limited(current, pwm);
+        }
+
+        self.write(&command::pwm(register))?;
+        self.shadow.pwm = register;
+        self.shadow.dirty.pwm = false;
+        Ok(())
+    }
 
-        writebuf[0] = register::Register::Matrix2Begin as u8 + start_row;
-        writebuf[1..final_write_len].copy_from_slice(rows);
+    /// Write pixels for the first matrix. Call update to flush updates.
+    pub fn set_matrix1_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        command::check_rows(start_row, rows)?;
+        self.write_rows(register::Register::Matrix1Begin, start_row, rows)?;
+        shadow::copy_rows(&mut self.shadow.matrix1, start_row as usize, rows);
+        self.shadow.dirty.matrix1.remove_range(start_row as usize, start_row as usize + rows.len());
+        Ok(())
+    }
 
-        self.i2c.write(self.address as u8, &writebuf[..final_write_len])
+    /// Write pixels for the second matrix. Call update to flush updates.
+    pub fn set_matrix2_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        command::check_rows(start_row, rows)?;
+        self.write_rows(register::Register::Matrix2Begin, start_row, rows)?;
+        shadow::copy_rows(&mut self.shadow.matrix2, start_row as usize, rows);
+        self.shadow.dirty.matrix2.remove_range(start_row as usize, start_row as usize + rows.len());
+        Ok(())
     }
 
     /// Flush display updates.
-    pub fn update(&mut self) -> Result<(), E> {
-        self.i2c.write(self.address as u8, &[register::Register::UpdateColumn as u8, 0b00000000])
+    pub fn update(&mut self) -> Result<(), DeviceError<E>> {
+        self.write(&command::update())?;
+        self.shadow.dirty.update = false;
+        Ok(())
     }
 
     /// Reset device.
-    pub fn reset(&mut self) -> Result<(), E> {
-        self.i2c.write(self.address as u8, &[register::Register::Reset as u8, 0b00000000])
+    pub fn reset(&mut self) -> Result<(), DeviceError<E>> {
+        self.write(&command::reset())?;
+        self.shadow = Shadow::default();
+        let (current, pwm) = (self.shadow.lighting.current(), self.pwm());
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.current = current;
+            limiter.pwm = pwm;
+        }
+        Ok(())
     }
 
     /// Get lighting configuration.
     pub fn lighting(&self) -> Lighting {
-        self.lighting
+        self.shadow.lighting
     }
 
     /// Modify lighting configuration and send it to device.
-    pub fn modify_lighting<F>(&mut self, mut modify: F) -> Result<(), E>
+    ///
+    /// With a current limit, modify sees the requested row current, which is remembered
+    /// and lowered to fit the shown frame.
+    pub fn modify_lighting<F>(&mut self, mut modify: F) -> Result<(), DeviceError<E>>
         where F: FnMut(&mut Lighting) -> &mut Lighting {
-        let mut lighting = self.lighting;
+        let mut lighting = self.requested_lighting();
         modify(&mut lighting);
-        self.i2c.write(self.address as u8, &[register::Register::LightingEffect as u8, lighting.byte()])
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.current = lighting.current();
+        }
+
+        let limited = self.limited_output(&self.shadow_frame());
+        if let Some((current, _)) = limited {
+            lighting.set_current(current);
+        }
+        self.write(&command::lighting(lighting))?;
+        self.shadow.lighting = lighting;
+        self.shadow.dirty.lighting = false;
+
+        match limited {
+            Some((current, pwm)) => self.write_limited(current, pwm),
+            None => Ok(()),
+        }
     }
 
     /// Get device configuration.
     pub fn config(&self) -> Configuration {
-        self.config
+        self.shadow.config
     }
 
     /// Modify device configuration and send it to device.
-    pub fn modify_config<F>(&mut self, mut modify: F) -> Result<(), E>
+    pub fn modify_config<F>(&mut self, mut modify: F) -> Result<(), DeviceError<E>>
+        where F: FnMut(&mut Configuration) -> &mut Configuration {
+        let mut config = self.shadow.config;
+        modify(&mut config);
+        self.write(&command::config(config))?;
+        self.shadow.config = config;
+        self.shadow.dirty.config = false;
+        Ok(())
+    }
+
+    /// Let the audio input modulate matrix intensity, with given gain and row current.
+    pub fn enable_audio(&mut self, settings: AudioSettings) -> Result<(), DeviceError<E>> {
+        self.modify_lighting(|l| l
+            .set_current(settings.current())
+            .set_audio_gain(settings.gain()))?;
+        self.modify_config(|c| c.set_audio(ConfigAudio::Signal))
+    }
+
+    /// Control matrix intensity by the current setting in the lighting effect register again.
+    pub fn disable_audio(&mut self) -> Result<(), DeviceError<E>> {
+        self.modify_config(|c| c.set_audio(ConfigAudio::LightingEffect))
+    }
+
+    /// Set row current and PWM duty for perceptual brightness.
+    ///
+    /// The register that lowers light output is written first, so that the output
+    /// never overshoots in between. Unchanged registers are not sent.
+    pub fn set_brightness(&mut self, brightness: Brightness) -> Result<(), DeviceError<E>> {
+        let current = brightness.current();
+        let pwm = brightness.pwm();
+        let (requested_current, requested_pwm) = self.requested_output();
+        let current_changed = requested_current != current || self.shadow.dirty.lighting;
+        let pwm_changed = requested_pwm != pwm || self.shadow.dirty.pwm;
+
+        if current.milliamps() > requested_current.milliamps() {
+            if pwm_changed {
+                self.set_pwm(pwm)?;
+            }
+            self.modify_lighting(|l| l.set_current(current))?;
+        } else {
+            if current_changed {
+                self.modify_lighting(|l| l.set_current(current))?;
+            }
+            if pwm_changed {
+                self.set_pwm(pwm)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if device is in software shutdown.
+    pub fn is_shutdown(&self) -> bool {
+        self.shadow.config.software_shutdown()
+    }
+
+    /// Enter software shutdown. Matrix data is kept and can still be modified.
+    pub fn shutdown(&mut self) -> Result<(), DeviceError<E>> {
+        self.modify_config(|c| c.set_software_shutdown(true))
+    }
+
+    /// Leave software shutdown and flush changes buffered while asleep.
+    pub fn wake(&mut self) -> Result<(), DeviceError<E>> {
+        self.modify_config(|c| c.set_software_shutdown(false))?;
+        self.flush()
+    }
+
+    /// Enter software shutdown until the returned guard is dropped or woken.
+    pub fn sleep(&mut self) -> Result<ShutdownGuard<'_, I2C>, DeviceError<E>> {
+        self.shutdown()?;
+        Ok(ShutdownGuard::new(self))
+    }
+
+    /// Get PWM duty (0 - 128), as sent or buffered.
+    pub fn pwm(&self) -> u8 {
+        if self.shadow.pwm & 0b1000_0000 > 0 { 128 } else { self.shadow.pwm }
+    }
+
+    /// Get first matrix rows, as sent or buffered.
+    pub fn matrix1_rows(&self) -> &[u8] {
+        &self.shadow.matrix1
+    }
+
+    /// Get second matrix rows, as sent or buffered.
+    pub fn matrix2_rows(&self) -> &[u8] {
+        &self.shadow.matrix2
+    }
+
+    /// Set PWM duty without sending it. Call flush to send changes.
+    pub fn buffer_pwm(&mut self, value: u8) -> Result<(), DeviceError<E>> {
+        let register = command::pwm_register(value)?;
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.pwm = value;
+        }
+
+        match self.limited_output(&self.shadow_frame()) {
+            Some((current, pwm)) => self.buffer_limited(current, pwm),
+            None => if self.shadow.pwm != register {
+                self.shadow.pwm = register;
+                self.shadow.dirty.pwm = true;
+            },
+        }
+        Ok(())
+    }
+
+    /// Modify lighting configuration without sending it. Call flush to send changes.
+    pub fn buffer_lighting<F>(&mut self, mut modify: F)
+        where F: FnMut(&mut Lighting) -> &mut Lighting {
+        let mut lighting = self.requested_lighting();
+        modify(&mut lighting);
+        if let Some(limiter) = self.limiter.as_mut() {
+            limiter.current = lighting.current();
+        }
+
+        if self.shadow.lighting.byte() != lighting.byte() {
+            self.shadow.lighting = lighting;
+            self.shadow.dirty.lighting = true;
+        }
+        if let Some((current, pwm)) = self.limited_output(&self.shadow_frame()) {
+            self.buffer_limited(current, pwm);
+        }
+    }
+
+    /// Modify device configuration without sending it. Call flush to send changes.
+    pub fn buffer_config<F>(&mut self, mut modify: F)
         where F: FnMut(&mut Configuration) -> &mut Configuration {
-        let mut config = self.config;
+        let mut config = self.shadow.config;
         modify(&mut config);
-        self.i2c.write(self.address as u8, &[register::Register::Config as u8, config.byte()])
+        if self.shadow.config.byte() != config.byte() {
+            self.shadow.config = config;
+            self.shadow.dirty.config = true;
+        }
+    }
+
+    /// Write pixels for the first matrix without sending them. Only changed rows are marked for flush.
+    pub fn buffer_matrix1_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        command::check_rows(start_row, rows)?;
+        shadow::buffer_rows(&mut self.shadow.matrix1, &mut self.shadow.dirty.matrix1, start_row as usize, rows);
+        Ok(())
+    }
+
+    /// Write pixels for the second matrix without sending them. Only changed rows are marked for flush.
+    pub fn buffer_matrix2_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        command::check_rows(start_row, rows)?;
+        shadow::buffer_rows(&mut self.shadow.matrix2, &mut self.shadow.dirty.matrix2, start_row as usize, rows);
+        Ok(())
+    }
+
+    /// Check if there are buffered changes not yet sent to device.
+    pub fn is_dirty(&self) -> bool {
+        self.shadow.dirty.any()
+    }
+
+    /// Mark all registers as changed, so that the next flush sends the whole state.
+    ///
+    /// Use this when device might have lost its state, for example, after a brown-out.
+    pub fn invalidate(&mut self) {
+        self.shadow.dirty = Dirty::all();
+    }
+
+    /// Send buffered changes to device.
+    ///
+    /// Only changed registers and the smallest row ranges covering changed rows are written,
+    /// followed by a single update if any matrix data was sent.
+    pub fn flush(&mut self) -> Result<(), DeviceError<E>> {
+        if self.shadow.dirty.config {
+            self.write(&command::config(self.shadow.config))?;
+            self.shadow.dirty.config = false;
+        }
+
+        if self.shadow.dirty.lighting {
+            self.write(&command::lighting(self.shadow.lighting))?;
+            self.shadow.dirty.lighting = false;
+        }
+
+        if self.shadow.dirty.pwm {
+            self.write(&command::pwm(self.shadow.pwm))?;
+            self.shadow.dirty.pwm = false;
+        }
+
+        self.flush_rows()
+    }
+
+    /// Show frame: send rows that differ from the current contents and latch both matrices
+    /// with a single update.
+    ///
+    /// Other buffered changes, like configuration or PWM, are not sent, unless the current
+    /// limit has to change them.
+    pub fn present(&mut self, frame: &Frame) -> Result<(), DeviceError<E>> {
+        let limited = self.limited_output(frame);
+
+        // Lower the output before the frame is shown, raise it after.
+        if let Some((current, pwm)) = limited {
+            let (current, pwm) = self.lowered_output(current, pwm);
+            self.write_limited(current, pwm)?;
+        }
+
+        shadow::buffer_rows(&mut self.shadow.matrix1, &mut self.shadow.dirty.matrix1, 0, frame.matrix1());
+        shadow::buffer_rows(&mut self.shadow.matrix2, &mut self.shadow.dirty.matrix2, 0, frame.matrix2());
+        self.flush_rows()?;
+
+        match limited {
+            Some((current, pwm)) => self.write_limited(current, pwm),
+            None => Ok(()),
+        }
+    }
+
+    /// Encode frame as a complete update of both matrices into batch, to be sent by an
+    /// interrupt or DMA driven bus instead of this device.
+    ///
+    /// With a current limit, row current and PWM duty are encoded as `present` writes them,
+    /// lowered before the matrices and raised after, in up to `LIMITED_FRAME_TRANSFERS`
+    /// transfers. Nothing is encoded if they do not all fit.
+    ///
+    /// The frame is recorded as shown. Call `invalidate` if the transfers fail.
+    pub fn encode_frame(&mut self, frame: &Frame, batch: &mut batch::Batch) -> Result<(), batch::BatchFull> {
+        let shadow = self.shadow;
+        let len = batch.len();
+        let result = self.encode_limited_frame(frame, batch);
+        if result.is_err() {
+            self.shadow = shadow;
+            batch.truncate(len);
+        }
+        result
+    }
+
+    /// Keep estimated LED current of presented frames within limit, by lowering PWM duty and
+    /// row current below the values set on the device. `None` removes the limit.
+    ///
+    /// The limit is applied by `present` and `encode_frame`.
+    ///
+    /// Removing the limit buffers the requested row current and PWM duty, call flush to send them.
+    pub fn set_current_limit(&mut self, limit: Option<CurrentLimit>) {
+        let (current, pwm) = self.requested_output();
+        self.limiter = limit.map(|limit| power::Limiter {
+            limit,
+            current,
+            pwm,
+        });
+        if self.limiter.is_none() {
+            self.buffer_limited(current, pwm);
+        }
+    }
+
+    pub fn current_limit(&self) -> Option<CurrentLimit> {
+        self.limiter.map(|limiter| limiter.limit)
+    }
+
+    /// Row current and PWM duty set on the device, before the current limit is applied.
+    pub fn requested_output(&self) -> (LightingCurrent, u8) {
+        match self.limiter {
+            Some(limiter) => (limiter.current, limiter.pwm),
+            None => (self.shadow.lighting.current(), self.pwm()),
+        }
+    }
+
+    /// Lighting with the requested row current, as the caller set it.
+    fn requested_lighting(&self) -> Lighting {
+        let mut lighting = self.shadow.lighting;
+        lighting.set_current(self.requested_output().0);
+        lighting
+    }
+
+    /// Frame held by the matrix registers.
+    fn shadow_frame(&self) -> Frame {
+        let mut frame = Frame::new();
+        frame.matrix1_mut().copy_from_slice(&self.shadow.matrix1);
+        frame.matrix2_mut().copy_from_slice(&self.shadow.matrix2);
+        frame
+    }
+
+    /// Requested row current and PWM duty lowered by the current limit to fit frame.
+    fn limited_output(&self, frame: &Frame) -> Option<(LightingCurrent, u8)> {
+        self.limiter.map(|limiter| {
+            let lit = power::lit_count(frame, self.shadow.config);
+            limiter.limit.fit(lit, self.shadow.config.matrix_mode(), limiter.current, limiter.pwm)
+        })
+    }
+
+    /// Row current and PWM duty not above the shown ones nor the limited ones, safe to
+    /// keep while the frame changes.
+    fn lowered_output(&self, current: LightingCurrent, pwm: u8) -> (LightingCurrent, u8) {
+        let current = if current.milliamps() < self.shadow.lighting.current_milliamps() {
+            current
+        } else {
+            self.shadow.lighting.current()
+        };
+        (current, pwm.min(self.pwm()))
+    }
+
+    /// Buffer row current and PWM duty, marking those that differ for flush.
+    fn buffer_limited(&mut self, current: LightingCurrent, pwm: u8) {
+        if self.shadow.lighting.current() != current {
+            self.shadow.lighting.set_current(current);
+            self.shadow.dirty.lighting = true;
+        }
+        if self.pwm() != pwm {
+            self.shadow.pwm = pwm;
+            self.shadow.dirty.pwm = true;
+        }
+    }
+
+    /// Send row current and PWM duty chosen by the current limit, if they differ.
+    fn write_limited(&mut self, current: LightingCurrent, pwm: u8) -> Result<(), DeviceError<E>> {
+        if self.shadow.lighting.current() != current || self.shadow.dirty.lighting {
+            let mut lighting = self.shadow.lighting;
+            lighting.set_current(current);
+            self.write(&command::lighting(lighting))?;
+            self.shadow.lighting = lighting;
+            self.shadow.dirty.lighting = false;
+        }
+
+        let pwm = command::pwm_register(pwm)?;
+        if self.shadow.pwm != pwm || self.shadow.dirty.pwm {
+            self.write(&command::pwm(pwm))?;
+            self.shadow.pwm = pwm;
+            self.shadow.dirty.pwm = false;
+        }
+        Ok(())
+    }
+
+    /// Encode frame between the lowered and the final output of the current limit.
+    fn encode_limited_frame(&mut self, frame: &Frame, batch: &mut batch::Batch) -> Result<(), batch::BatchFull> {
+        let limited = self.limited_output(frame);
+        if let Some((current, pwm)) = limited {
+            let (current, pwm) = self.lowered_output(current, pwm);
+            self.encode_limited(current, pwm, batch)?;
+        }
+
+        let matrix_mode = self.shadow.config.matrix_mode();
+        batch.push_frame(self.address, matrix_mode, frame)?;
+
+        let height = matrix_mode.height();
+        shadow::copy_rows(&mut self.shadow.matrix1, 0, &frame.matrix1()[..height]);
+        shadow::copy_rows(&mut self.shadow.matrix2, 0, &frame.matrix2()[..height]);
+        self.shadow.dirty.matrix1.remove_range(0, height);
+        self.shadow.dirty.matrix2.remove_range(0, height);
+        self.shadow.dirty.update = false;
+
+        match limited {
+            Some((current, pwm)) => self.encode_limited(current, pwm, batch),
+            None => Ok(()),
+        }
+    }
+
+    /// Encode row current and PWM duty chosen by the current limit, if they differ.
+    fn encode_limited(&mut self, current: LightingCurrent, pwm: u8, batch: &mut batch::Batch) -> Result<(), batch::BatchFull> {
+        if self.shadow.lighting.current() != current || self.shadow.dirty.lighting {
+            let mut lighting = self.shadow.lighting;
+            lighting.set_current(current);
+            batch.push(self.address, &command::lighting(lighting))?;
+            self.shadow.lighting = lighting;
+            self.shadow.dirty.lighting = false;
+        }
+
+        if self.pwm() != pwm || self.shadow.dirty.pwm {
+            batch.push(self.address, &command::pwm(pwm))?;
+            self.shadow.pwm = pwm;
+            self.shadow.dirty.pwm = false;
+        }
+        Ok(())
+    }
+
+    /// Send changed rows of both matrices and latch them.
+    fn flush_rows(&mut self) -> Result<(), DeviceError<E>> {
+        if let Some((start, end)) = self.shadow.dirty.matrix1.span() {
+            let rows = self.shadow.matrix1;
+            self.write_rows(register::Register::Matrix1Begin, start as u8, &rows[start..end])?;
+            self.shadow.dirty.matrix1.remove_range(start, end);
+        }
+
+        if let Some((start, end)) = self.shadow.dirty.matrix2.span() {
+            let rows = self.shadow.matrix2;
+            self.write_rows(register::Register::Matrix2Begin, start as u8, &rows[start..end])?;
+            self.shadow.dirty.matrix2.remove_range(start, end);
+        }
+
+        if self.shadow.dirty.update {
+            self.update()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_rows(&mut self, begin: register::Register, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>> {
+        self.write(command::RowsCommand::new(begin, start_row, rows).bytes())?;
+        self.shadow.dirty.update = true;
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), DeviceError<E>> {
+        self.i2c.write(self.address as u8, bytes).map_err(DeviceError::Bus)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim::Chip;
+
+    fn device() -> Device<Chip> {
+        Device::new(Address::Address00, Chip::new(Address::Address00))
+    }
+
+    #[test]
+    fn modified_config_and_lighting_are_kept() {
+        let mut device = device();
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        device.modify_config(|c| c.set_matrix_mode(ConfigMatrixMode::Size5x11)).unwrap();
+        device.modify_lighting(|l| l.set_current(LightingCurrent::Current20mA)).unwrap();
+
+        assert_eq!(device.config().byte(), 0b0001_1011);
+        assert_eq!(device.lighting().byte(), 0b0000_1011);
+        assert_eq!(device.i2c().config(), 0b0001_1011);
+        assert_eq!(device.i2c().lighting(), 0b0000_1011);
+    }
+
+    #[test]
+    fn audio_mode_bytes() {
+        let mut device = device();
+        device.modify_config(|c| c
+            .set_matrix_mode(ConfigMatrixMode::Size6x10)
+            .set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+
+        let settings = AudioSettings::new(LightingAudioGain::Gain12dB, LightingCurrent::Current15mA);
+        device.enable_audio(settings).unwrap();
+        assert_eq!(device.i2c().lighting(), 0b0100_1010);
+        assert_eq!(device.i2c().config(), 0b0001_1110);
+        assert!(device.i2c().is_audio_enabled());
+
+        device.disable_audio().unwrap();
+        assert_eq!(device.i2c().lighting(), 0b0100_1010);
+        assert_eq!(device.i2c().config(), 0b0001_1010);
+        assert!(!device.i2c().is_audio_enabled());
+    }
+
+    #[test]
+    fn pwm_duty() {
+        let mut device = device();
+        assert_eq!(device.pwm(), 128);
+
+        device.set_pwm(0).unwrap();
+        assert_eq!(device.i2c().pwm(), 0);
+        device.set_pwm(127).unwrap();
+        assert_eq!(device.i2c().pwm(), 0b0111_1111);
+        device.set_pwm(128).unwrap();
+        assert_eq!(device.i2c().pwm(), 0b1000_0000);
+        assert_eq!(device.set_pwm(129), Err(DeviceError::InvalidPwm(129)));
+        assert_eq!(device.buffer_pwm(255), Err(DeviceError::InvalidPwm(255)));
+        assert_eq!(device.i2c().write_count(), 3);
+        assert_eq!(device.pwm(), 128);
+    }
+
+    #[test]
+    fn brightness_sets_current_and_pwm() {
+        let mut device = device();
+        device.set_brightness(Brightness(255)).unwrap();
+        assert_eq!(device.i2c().lighting() & 0b1111, LightingCurrent::Current75mA as u8);
+        assert_eq!(device.i2c().pwm_duty(), 128);
+        assert_eq!(device.i2c().write_count(), 1);
+
+        device.set_brightness(Brightness(10)).unwrap();
+        assert_eq!(device.i2c().lighting() & 0b1111, LightingCurrent::Current5mA as u8);
+        assert_eq!(device.i2c().pwm_duty(), Brightness(10).pwm());
+        assert_eq!(device.i2c().write_count(), 3);
+
+        device.set_brightness(Brightness(10)).unwrap();
+        assert_eq!(device.i2c().write_count(), 3);
+    }
+
+    #[test]
+    fn rows_past_last_register_are_rejected() {
+        let mut device = device();
+        device.set_matrix1_rows(8, &[1, 2, 3]).unwrap();
+
+        assert_eq!(device.set_matrix1_rows(9, &[1, 2, 3]), Err(DeviceError::InvalidRowRange { start_row: 9, len: 3 }));
+        assert_eq!(device.set_matrix2_rows(0, &[0; 12]), Err(DeviceError::InvalidRowRange { start_row: 0, len: 12 }));
+        assert_eq!(device.buffer_matrix2_rows(11, &[1]), Err(DeviceError::InvalidRowRange { start_row: 11, len: 1 }));
+        assert_eq!(device.i2c().write_count(), 1);
+        assert_eq!(device.i2c().lighting(), 0);
+        assert_eq!(device.matrix2_rows(), &[0; 11]);
+    }
+
+    #[test]
+    fn shutdown_keeps_frame() {
+        let mut device = device();
+        device.set_matrix1_rows(0, &[0b1010_1010]).unwrap();
+        device.update().unwrap();
+
+        device.shutdown().unwrap();
+        assert!(device.is_shutdown());
+        assert_eq!(device.i2c().config(), 0b1000_0000);
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+
+        device.wake().unwrap();
+        assert!(!device.is_shutdown());
+        assert_eq!(device.i2c().config(), 0b0000_0000);
+        assert_eq!(device.i2c().visible_matrix1().rows()[0], 0b1010_1010);
+    }
+
+    #[test]
+    fn sleep_guard_wakes_with_buffered_frame() {
+        let mut device = device();
+        device.set_matrix1_rows(0, &[0xff]).unwrap();
+        device.update().unwrap();
+
+        {
+            let mut asleep = device.sleep().unwrap();
+            asleep.buffer_matrix1_rows(1, &[0x0f]).unwrap();
+            assert!(asleep.i2c().is_shutdown());
+            assert_eq!(asleep.i2c().visible_matrix1().lit_count(), 0);
+        }
+
+        assert!(!device.i2c().is_shutdown());
+        assert_eq!(&device.i2c().visible_matrix1().rows()[..2], &[0xff, 0x0f]);
+
+        let asleep = device.sleep().unwrap();
+        asleep.wake().unwrap();
+        assert!(!device.i2c().is_shutdown());
+    }
+
+    #[test]
+    fn flush_sends_only_changes() {
+        let mut device = device();
+        device.buffer_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2));
+        device.buffer_matrix1_rows(0, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        device.buffer_matrix2_rows(0, &[8, 7, 6, 5, 4, 3, 2, 1]).unwrap();
+        assert!(device.is_dirty());
+        device.flush().unwrap();
+
+        assert!(!device.is_dirty());
+        assert_eq!(device.i2c().write_count(), 4);
+        assert_eq!(device.i2c().visible_matrix1().rows(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(device.i2c().visible_matrix2().rows(), &[8, 7, 6, 5, 4, 3, 2, 1]);
+
+        device.buffer_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2));
+        device.buffer_matrix1_rows(0, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert!(!device.is_dirty());
+        device.flush().unwrap();
+        assert_eq!(device.i2c().write_count(), 4);
+
+        device.buffer_matrix1_rows(0, &[1, 2, 0, 4, 0, 6, 7, 8]).unwrap();
+        device.flush().unwrap();
+        assert_eq!(device.i2c().write_count(), 6);
+        assert_eq!(device.i2c().byte_count(), 2 + 9 + 9 + 2 + (1 + 3) + 2);
+        assert_eq!(device.i2c().visible_matrix1().rows(), &[1, 2, 0, 4, 0, 6, 7, 8]);
+    }
+
+    #[test]
+    fn flush_latches_rows_sent_directly() {
+        let mut device = device();
+        device.set_matrix1_rows(0, &[0xff]).unwrap();
+        assert!(device.is_dirty());
+        device.flush().unwrap();
+
+        assert_eq!(device.i2c().visible_matrix1().rows()[0], 0xff);
+        assert_eq!(device.i2c().write_count(), 2);
+    }
+
+    #[test]
+    fn invalidate_resends_everything() {
+        let mut device = device();
+        device.buffer_matrix1_rows(3, &[0xf0]).unwrap();
+        device.buffer_pwm(0x10).unwrap();
+        device.flush().unwrap();
+        device.reset().unwrap();
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+
+        device.buffer_matrix1_rows(3, &[0xf0]).unwrap();
+        device.buffer_pwm(0x10).unwrap();
+        device.invalidate();
+        device.flush().unwrap();
+        assert_eq!(device.i2c().visible_matrix1().rows()[3], 0xf0);
+        assert_eq!(device.i2c().pwm_duty(), 0x10);
+    }
+
+    #[test]
+    fn present_keeps_current_within_limit() {
+        let mut device = device();
+        device.set_current_limit(Some(CurrentLimit::new(100)));
+        let mut bright = Frame::new();
+        bright.matrix1_mut()[..8].copy_from_slice(&[0xff; 8]);
+        let mut dim = Frame::new();
+        dim.matrix1_mut()[0] = 0xff;
+
+        device.present(&bright).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 40);
+        assert_eq!(device.requested_output(), (LightingCurrent::Current40mA, 128));
+
+        device.present(&dim).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 128);
+
+        device.set_pwm(64).unwrap();
+        device.present(&bright).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 40);
+        device.present(&dim).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 64);
+
+        device.set_current_limit(None);
+        device.present(&bright).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 64);
+        assert_eq!(device.current_limit(), None);
+    }
+
+    #[test]
+    fn limited_output_keeps_request() {
+        let mut device = device();
+        device.set_current_limit(Some(CurrentLimit::new(100)));
+        let mut bright = Frame::new();
+        bright.matrix1_mut()[..8].copy_from_slice(&[0xff; 8]);
+
+        device.present(&bright).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 40);
+        device.modify_lighting(|l| l.set_audio_gain(LightingAudioGain::Gain12dB)).unwrap();
+        device.buffer_lighting(|l| l.set_audio_gain(LightingAudioGain::Gain6dB));
+        device.present(&Frame::new()).unwrap();
+
+        assert_eq!(device.requested_output(), (LightingCurrent::Current40mA, 128));
+        assert_eq!(device.i2c().pwm_duty(), 128);
+    }
+
+    #[test]
+    fn setters_stay_within_limit() {
+        let mut device = device();
+        device.set_current_limit(Some(CurrentLimit::new(100)));
+        let mut bright = Frame::new();
+        bright.matrix1_mut()[..8].copy_from_slice(&[0xff; 8]);
+        device.present(&bright).unwrap();
+
+        device.set_pwm(100).unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 40);
+        device.modify_lighting(|l| l.set_current(LightingCurrent::Current75mA)).unwrap();
+        assert_eq!(device.i2c().lighting(), LightingCurrent::Current75mA as u8);
+        assert_eq!(device.i2c().pwm_duty(), 21);
+        device.buffer_pwm(128).unwrap();
+        device.flush().unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 21);
+        assert_eq!(device.requested_output(), (LightingCurrent::Current75mA, 128));
+
+        device.set_current_limit(None);
+        device.flush().unwrap();
+        assert_eq!(device.i2c().pwm_duty(), 128);
     }
 }
\ No newline at end of file
@@ -1,4 +1,13 @@
-#[derive(Copy, Clone)]
+use core::convert::TryFrom;
+use core::fmt;
+use crate::InvalidEncoding;
+
+/// Bit that must be zero.
+const RESERVED: u8 = 0b1000_0000;
+const CURRENT: u8 = 0b1111;
+const AUDIO_GAIN: u8 = 0b111_0000;
+
+#[derive(Copy, Clone, PartialEq)]
 pub struct Lighting {
     value: u8,
 }
@@ -16,13 +25,53 @@ impl Lighting {
         self.value
     }
 
+    pub fn current(&self) -> LightingCurrent {
+        use LightingCurrent::*;
+        match self.value & CURRENT {
+            0b1000 => Current5mA,
+            0b1001 => Current10mA,
+            0b1010 => Current15mA,
+            0b1011 => Current20mA,
+            0b1100 => Current25mA,
+            0b1101 => Current30mA,
+            0b1110 => Current35mA,
+            0b0000 => Current40mA,
+            0b0001 => Current45mA,
+            0b0010 => Current50mA,
+            0b0011 => Current55mA,
+            0b0100 => Current60mA,
+            0b0101 => Current65mA,
+            0b0110 => Current70mA,
+            _ => Current75mA,
+        }
+    }
+
+    /// Row current in mA.
+    pub fn current_milliamps(&self) -> u8 {
+        self.current().milliamps()
+    }
+
     pub fn set_current(&mut self, value: LightingCurrent) -> &mut Self {
-        self.set_bits(0b1111, value as u8);
+        self.set_bits(CURRENT, value as u8);
         self
     }
 
+    pub fn audio_gain(&self) -> LightingAudioGain {
+        use LightingAudioGain::*;
+        match self.value & AUDIO_GAIN {
+            0b000_0000 => Gain0dB,
+            0b001_0000 => Gain3dB,
+            0b010_0000 => Gain6dB,
+            0b011_0000 => Gain9dB,
+            0b100_0000 => Gain12dB,
+            0b101_0000 => Gain15dB,
+            0b110_0000 => Gain18dB,
+            _ => GainMinus6dB,
+        }
+    }
+
     pub fn set_audio_gain(&mut self, value: LightingAudioGain) -> &mut Self {
-        self.set_bits(0b111_0000, value as u8);
+        self.set_bits(AUDIO_GAIN, value as u8);
         self
     }
 
@@ -31,7 +80,34 @@ impl Lighting {
     }
 }
 
-#[derive(Copy, Clone)]
+impl TryFrom<u8> for Lighting {
+    type Error = InvalidEncoding;
+
+    /// Decode register byte, rejecting the reserved bit and current `0b1111`.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value & RESERVED != 0 || value & CURRENT == 0b1111 {
+            return Err(InvalidEncoding(value));
+        }
+        Ok(Lighting { value })
+    }
+}
+
+impl fmt::Debug for Lighting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lighting")
+            .field("current", &self.current())
+            .field("audio_gain", &self.audio_gain())
+            .finish()
+    }
+}
+
+impl fmt::Display for Lighting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, audio gain {}", self.current(), self.audio_gain())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum LightingCurrent {
     Current5mA  = 0b1000,
@@ -51,7 +127,52 @@ pub enum LightingCurrent {
     Current75mA = 0b0111,
 }
 
-#[derive(Copy, Clone)]
+impl LightingCurrent {
+    /// Row current in mA.
+    pub fn milliamps(&self) -> u8 {
+        milliamps(*self as u8)
+    }
+
+    /// Current setting for mA in 5 mA steps (5 - 75).
+    pub fn from_milliamps(milliamps: u8) -> Option<LightingCurrent> {
+        use LightingCurrent::*;
+        Some(match milliamps {
+            5 => Current5mA,
+            10 => Current10mA,
+            15 => Current15mA,
+            20 => Current20mA,
+            25 => Current25mA,
+            30 => Current30mA,
+            35 => Current35mA,
+            40 => Current40mA,
+            45 => Current45mA,
+            50 => Current50mA,
+            55 => Current55mA,
+            60 => Current60mA,
+            65 => Current65mA,
+            70 => Current70mA,
+            75 => Current75mA,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for LightingCurrent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} mA", self.milliamps())
+    }
+}
+
+fn milliamps(bits: u8) -> u8 {
+    // 0b1000 - 0b1110 encode 5 - 35 mA, 0b0000 - 0b0111 encode 40 - 75 mA.
+    if bits & 0b1000 > 0 {
+        ((bits & 0b0111) + 1) * 5
+    } else {
+        (bits + 8) * 5
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum LightingAudioGain {
     /// 0dB
@@ -70,4 +191,71 @@ pub enum LightingAudioGain {
     Gain18dB = 0b110_0000,
     /// -6dB
     GainMinus6dB = 0b111_0000,
+}
+
+impl LightingAudioGain {
+    /// Gain in dB.
+    pub fn decibels(&self) -> i8 {
+        match self {
+            LightingAudioGain::GainMinus6dB => -6,
+            gain => (*gain as u8 >> 4) as i8 * 3,
+        }
+    }
+}
+
+impl fmt::Display for LightingAudioGain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:+} dB", self.decibels())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::format;
+
+    #[test]
+    fn current_milliamps() {
+        assert_eq!(LightingCurrent::Current5mA.milliamps(), 5);
+        assert_eq!(LightingCurrent::Current35mA.milliamps(), 35);
+        assert_eq!(LightingCurrent::Current40mA.milliamps(), 40);
+        assert_eq!(LightingCurrent::Current75mA.milliamps(), 75);
+    }
+
+    #[test]
+    fn current_from_milliamps() {
+        for milliamps in (5..=75).step_by(5) {
+            assert_eq!(LightingCurrent::from_milliamps(milliamps).unwrap().milliamps(), milliamps);
+        }
+        assert!(LightingCurrent::from_milliamps(0).is_none());
+        assert!(LightingCurrent::from_milliamps(12).is_none());
+
+        let mut lighting = Lighting::default();
+        assert_eq!(lighting.current_milliamps(), 40);
+        lighting.set_current(LightingCurrent::Current15mA);
+        assert_eq!(lighting.current_milliamps(), 15);
+    }
+
+    #[test]
+    fn decode_register() {
+        let lighting = Lighting::try_from(0b0111_0010).unwrap();
+        assert_eq!(lighting.current(), LightingCurrent::Current50mA);
+        assert_eq!(lighting.audio_gain(), LightingAudioGain::GainMinus6dB);
+        assert_eq!(lighting.byte(), 0b0111_0010);
+
+        assert_eq!(Lighting::try_from(0b1000_0000), Err(InvalidEncoding(0b1000_0000)));
+        assert_eq!(Lighting::try_from(0b0000_1111), Err(InvalidEncoding(0b0000_1111)));
+    }
+
+    #[test]
+    fn readable() {
+        let mut lighting = Lighting::default();
+        lighting.set_current(LightingCurrent::Current5mA).set_audio_gain(LightingAudioGain::Gain9dB);
+        assert_eq!(format!("{}", lighting), "5 mA, audio gain +9 dB");
+        assert_eq!(format!("{:?}", lighting), "Lighting { current: Current5mA, audio_gain: Gain9dB }");
+        assert_eq!(format!("{}", LightingAudioGain::GainMinus6dB), "-6 dB");
+        assert_eq!(format!("{}", LightingAudioGain::Gain0dB), "+0 dB");
+    }
 }
\ No newline at end of file
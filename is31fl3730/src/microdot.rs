@@ -0,0 +1,247 @@
+//! Pimoroni Micro Dot pHAT: three chips driving six 5x7 matrices with a decimal point each.
+//!
+//! Digits are numbered left to right. Each chip drives two digits, the left one on its
+//! second matrix. Matrices run in `Size8x8` mode and are wired differently:
+//!
+//! - first matrix: one register per digit column, digit rows in bits 0 - 6,
+//!   decimal point in bit 7 of register 6;
+//! - second matrix: one register per digit row, digit columns in bits 0 - 4,
+//!   decimal point in bit 6 of register 7.
+
+use crate::{Address, Brightness, ConfigMatrixMode, ConfigDisplayMode, Device, DeviceError, Frame};
+use crate::array::ArrayError;
+use crate::display::MatrixBank;
+use crate::layout::{Layout, Placement, Rotation};
+use crate::pixels::DataBits;
+
+/// Width of the display in pixels.
+pub const WIDTH: usize = 30;
+/// Height of the display in pixels.
+pub const HEIGHT: usize = 7;
+/// Number of digits.
+pub const DIGITS: usize = 6;
+/// Chip addresses, left to right, as wired on the board: 0x63, 0x62 and 0x61.
+pub const ADDRESSES: [Address; 3] = [Address::Address11, Address::Address10, Address::Address01];
+
+/// Placement of the digits on a `WIDTH` x `HEIGHT` canvas.
+pub const LAYOUT: Layout<'static> = Layout {
+    matrix_mode: ConfigMatrixMode::Size8x8,
+    placements: &[
+        Placement { address: Address::Address11, bank: MatrixBank::Matrix2, x: 0, y: 0, rotation: Rotation::Deg0, mirror: true },
+        Placement { address: Address::Address11, bank: MatrixBank::Matrix1, x: 5, y: 0, rotation: Rotation::Deg270, mirror: false },
+        Placement { address: Address::Address10, bank: MatrixBank::Matrix2, x: 10, y: 0, rotation: Rotation::Deg0, mirror: true },
+        Placement { address: Address::Address10, bank: MatrixBank::Matrix1, x: 15, y: 0, rotation: Rotation::Deg270, mirror: false },
+        Placement { address: Address::Address01, bank: MatrixBank::Matrix2, x: 20, y: 0, rotation: Rotation::Deg0, mirror: true },
+        Placement { address: Address::Address01, bank: MatrixBank::Matrix1, x: 25, y: 0, rotation: Rotation::Deg270, mirror: false },
+    ],
+};
+
+/// Digit LEDs of the first matrix: registers 0 - 4, bits 0 - 6.
+const MATRIX1_COLUMNS: usize = 5;
+const MATRIX1_MASK: u8 = 0b0111_1111;
+/// Digit LEDs of the second matrix: registers 0 - 6, bits 0 - 4.
+const MATRIX2_ROWS: usize = 7;
+const MATRIX2_MASK: u8 = 0b0001_1111;
+
+/// Decimal point registers and bits.
+const MATRIX1_DECIMAL: (usize, u8) = (6, 0b1000_0000);
+const MATRIX2_DECIMAL: (usize, u8) = (7, 0b0100_0000);
+
+/// The three chips of the board, left to right, shown as a single `WIDTH` x `HEIGHT`
+/// display with a decimal point after every digit.
+pub struct MicroDot<I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    devices: [Device<I2C>; 3],
+    decimals: [bool; DIGITS],
+}
+
+impl<I2C, E> MicroDot<I2C>
+    where
+        I2C: hal::i2c::I2c<Error = E>,
+{
+    /// Create from buses of the left, middle and right chips. Several may share one bus.
+    pub fn new(left: I2C, middle: I2C, right: I2C) -> MicroDot<I2C> {
+        MicroDot {
+            devices: [
+                Device::new(ADDRESSES[0], left),
+                Device::new(ADDRESSES[1], middle),
+                Device::new(ADDRESSES[2], right),
+            ],
+            decimals: [false; DIGITS],
+        }
+    }
+
+    pub fn devices(&self) -> &[Device<I2C>; 3] {
+        &self.devices
+    }
+
+    pub fn device_mut(&mut self, index: usize) -> Option<&mut Device<I2C>> {
+        self.devices.get_mut(index)
+    }
+
+    /// Release all devices.
+    pub fn release(self) -> [Device<I2C>; 3] {
+        self.devices
+    }
+
+    /// Reset all chips and configure them to show both matrices.
+    pub fn init(&mut self) -> Result<(), ArrayError<E>> {
+        self.each(|device| {
+            device.reset()?;
+            device.modify_config(|c| c
+                .set_matrix_mode(ConfigMatrixMode::Size8x8)
+                .set_display_mode(ConfigDisplayMode::Matrix1and2))
+        })
+    }
+
+    /// Set brightness of all chips.
+    pub fn set_brightness(&mut self, brightness: Brightness) -> Result<(), ArrayError<E>> {
+        self.each(|device| device.set_brightness(brightness))
+    }
+
+    /// Turn decimal point of digit (0 - 5) on or off. Shown with the next `output_pixels`.
+    pub fn set_decimal(&mut self, digit: usize, on: bool) {
+        if let Some(decimal) = self.decimals.get_mut(digit) {
+            *decimal = on;
+        }
+    }
+
+    pub fn decimal(&self, digit: usize) -> bool {
+        self.decimals.get(digit).cloned().unwrap_or(false)
+    }
+
+    /// Output the top-left `WIDTH` x `HEIGHT` pixels of data and the decimal points.
+    ///
+    /// Each chip is sent only changed rows, followed by an update if anything changed.
+    pub fn output_pixels<DATA>(&mut self, data: &DATA) -> Result<(), ArrayError<E>>
+        where
+            DATA: DataBits
+    {
+        let decimals = self.decimals;
+        self.each(|device| {
+            let frame = frame(device.address(), data, &decimals);
+            device.present(&frame)
+        })
+    }
+
+    /// Run operation on every chip, even if some fail. Returns the first error.
+    fn each<F>(&mut self, mut operation: F) -> Result<(), ArrayError<E>>
+        where F: FnMut(&mut Device<I2C>) -> Result<(), DeviceError<E>> {
+        let mut result = Ok(());
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            if let Err(error) = operation(device) {
+                if result.is_ok() {
+                    result = Err(ArrayError { index, error });
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Contents of the chip at address, with the spare bits cleared and decimal points set.
+fn frame<DATA>(address: Address, data: &DATA, decimals: &[bool; DIGITS]) -> Frame
+    where
+        DATA: DataBits
+{
+    let mut frame = LAYOUT.frame(address, data);
+
+    for (index, row) in frame.matrix1_mut().iter_mut().enumerate() {
+        *row = if index < MATRIX1_COLUMNS { *row & MATRIX1_MASK } else { 0 };
+    }
+    for (index, row) in frame.matrix2_mut().iter_mut().enumerate() {
+        *row = if index < MATRIX2_ROWS { *row & MATRIX2_MASK } else { 0 };
+    }
+
+    for (digit, placement) in LAYOUT.placements.iter().enumerate() {
+        if placement.address != address || !decimals[digit] {
+            continue;
+        }
+        match placement.bank {
+            MatrixBank::Matrix1 => frame.matrix1_mut()[MATRIX1_DECIMAL.0] |= MATRIX1_DECIMAL.1,
+            MatrixBank::Matrix2 => frame.matrix2_mut()[MATRIX2_DECIMAL.0] |= MATRIX2_DECIMAL.1,
+        }
+    }
+
+    frame
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W32, H8};
+
+    fn microdot() -> MicroDot<Chip> {
+        let mut microdot = MicroDot::new(
+            Chip::new(ADDRESSES[0]),
+            Chip::new(ADDRESSES[1]),
+            Chip::new(ADDRESSES[2]),
+        );
+        microdot.init().unwrap();
+        microdot
+    }
+
+    fn lit(chip: &Chip) -> u32 {
+        chip.visible_matrix1().lit_count() + chip.visible_matrix2().lit_count()
+    }
+
+    #[test]
+    fn digits_follow_wiring() {
+        let mut microdot = microdot();
+        let mut canvas = BitCanvas::<W32, H8>::new(32, 8).unwrap();
+        // Pixel (0, 0) of digit 0, pixel (1, 3) of digit 1 and pixel (4, 6) of digit 5.
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1000_0000, 0, 0, 0]);
+        canvas.row_mut(3).unwrap().copy_from_slice(&[0b0000_0010, 0, 0, 0]);
+        canvas.row_mut(6).unwrap().copy_from_slice(&[0, 0, 0, 0b0000_0100]);
+        microdot.output_pixels(&canvas).unwrap();
+
+        let chips = microdot.devices();
+        // Second matrix: register per row, column in bit x.
+        assert_eq!(chips[0].matrix2_rows()[0], 0b0000_0001);
+        // First matrix: register per column, row in bit y.
+        assert_eq!(chips[0].matrix1_rows()[1], 0b0000_1000);
+        assert_eq!(chips[2].matrix1_rows()[4], 0b0100_0000);
+        assert_eq!(lit(chips[0].i2c()), 2);
+        assert_eq!(lit(chips[1].i2c()), 0);
+        assert_eq!(lit(chips[2].i2c()), 1);
+    }
+
+    #[test]
+    fn spare_bits_are_not_lit() {
+        let mut microdot = microdot();
+        let mut canvas = BitCanvas::<W32, H8>::new(32, 8).unwrap();
+        for y in 0..8 {
+            canvas.row_mut(y).unwrap().copy_from_slice(&[0xff; 4]);
+        }
+        microdot.output_pixels(&canvas).unwrap();
+
+        for device in microdot.devices() {
+            assert_eq!(lit(device.i2c()), 70);
+        }
+    }
+
+    #[test]
+    fn decimal_points() {
+        let mut microdot = microdot();
+        let canvas = BitCanvas::<W32, H8>::new(32, 8).unwrap();
+        microdot.set_decimal(0, true);
+        microdot.set_decimal(3, true);
+        microdot.set_decimal(6, true);
+        assert!(microdot.decimal(3));
+        assert!(!microdot.decimal(6));
+        microdot.output_pixels(&canvas).unwrap();
+
+        let chips = microdot.devices();
+        assert_eq!(chips[0].matrix2_rows()[7], 0b0100_0000);
+        assert_eq!(chips[1].matrix1_rows()[6], 0b1000_0000);
+        assert_eq!(lit(chips[0].i2c()) + lit(chips[1].i2c()) + lit(chips[2].i2c()), 2);
+
+        microdot.set_decimal(0, false);
+        microdot.output_pixels(&canvas).unwrap();
+        assert_eq!(lit(microdot.devices()[0].i2c()), 0);
+    }
+}
@@ -25,6 +25,14 @@ pub trait DataBits {
         }
     }
 
+    fn offset_bits(&self, x: i16, y: i16) -> OffsetBits<Self> where Self: Sized {
+        OffsetBits {
+            inner: self,
+            offset_x: x,
+            offset_y: y,
+        }
+    }
+
     fn rotate_90(&self) -> Rotate90<Self> where Self: Sized {
         Rotate90 {
             inner: self,
@@ -165,6 +173,95 @@ impl Iterator for BytesIterRev {
     }
 }
 
+/// Pixels that are either lit or not.
+pub trait LitPixels {
+    fn lit(&self, x: i16, y: i16) -> bool;
+}
+
+/// Lit pixels as `DataBits`, `width` pixels wide.
+#[derive(Clone)]
+pub struct PixelBits<P> where P: LitPixels + Clone {
+    pixels: P,
+    width: i16,
+}
+
+impl<P> PixelBits<P> where P: LitPixels + Clone {
+    pub fn new(pixels: P, width: i16) -> PixelBits<P> {
+        PixelBits {
+            pixels,
+            width,
+        }
+    }
+
+    fn byte(&self, row: i16, pos: i16) -> u8 {
+        let mut byte: u8 = 0;
+        for bit in 0..8 {
+            let x = pos * 8 + bit;
+            if x >= 0 && x < self.width && self.pixels.lit(x, row) {
+                byte |= 0b1000_0000 >> bit;
+            }
+        }
+        byte
+    }
+}
+
+impl<P> DataBits for PixelBits<P> where P: LitPixels + Clone {
+    type BytesIter = PixelBitsIter<P>;
+    type BytesIterRev = PixelBitsIter<P>;
+
+    fn row_bits_len(&self) -> i16 {
+        self.width
+    }
+
+    fn row_bytes(&self, row: i16, range: Range<i16>) -> Self::BytesIter {
+        PixelBitsIter {
+            bits: self.clone(),
+            row,
+            pos: range.start,
+            end: range.end,
+            rev: false,
+        }
+    }
+
+    fn row_bytes_rev(&self, row: i16, range: Range<i16>) -> Self::BytesIterRev {
+        PixelBitsIter {
+            bits: self.clone(),
+            row,
+            pos: range.end - 1,
+            end: range.start,
+            rev: true,
+        }
+    }
+}
+
+pub struct PixelBitsIter<P> where P: LitPixels + Clone {
+    bits: PixelBits<P>,
+    row: i16,
+    pos: i16,
+    end: i16,
+    rev: bool,
+}
+
+impl<P> Iterator for PixelBitsIter<P> where P: LitPixels + Clone {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if (!self.rev && self.pos >= self.end) || (self.rev && self.pos < self.end) {
+            return None;
+        }
+
+        let byte = self.bits.byte(self.row, self.pos);
+
+        if self.rev {
+            self.pos -= 1;
+            Some(byte.reverse_bits())
+        } else {
+            self.pos += 1;
+            Some(byte)
+        }
+    }
+}
+
 pub struct FlipH<'f, I> where I: DataBits {
     inner: &'f I,
 }
@@ -209,6 +306,64 @@ impl<'f, I> DataBits for OffsetBytes<'f, I> where I: DataBits {
     }
 }
 
+/// Offset by pixels instead of whole bytes.
+pub struct OffsetBits<'f, I> where I: DataBits {
+    inner: &'f I,
+    offset_x: i16,
+    offset_y: i16,
+}
+
+impl<'f, I> DataBits for OffsetBits<'f, I> where I: DataBits {
+    type BytesIter = ShiftIter<I::BytesIter>;
+    type BytesIterRev = ShiftIter<I::BytesIterRev>;
+
+    fn row_bits_len(&self) -> i16 {
+        self.inner.row_bits_len()
+    }
+
+    fn row_bytes(&self, row: i16, range: Range<i16>) -> Self::BytesIter {
+        let (byte, bit) = byte_and_bit_for_bit_index(-self.offset_x);
+        let bytes = self.inner.row_bytes(row - self.offset_y, range.start + byte..range.end + byte + 1);
+        ShiftIter::new(bytes, bit, 8 - bit)
+    }
+
+    fn row_bytes_rev(&self, row: i16, range: Range<i16>) -> Self::BytesIterRev {
+        let (byte, bit) = byte_and_bit_for_bit_index(-self.offset_x);
+        let bytes = self.inner.row_bytes_rev(row - self.offset_y, range.start + byte..range.end + byte + 1);
+        ShiftIter::new(bytes, 8 - bit, bit)
+    }
+}
+
+/// Combines each byte with the high bits of the next one.
+pub struct ShiftIter<I> where I: Iterator<Item = u8> {
+    bytes: I,
+    current: u8,
+    left: u32,
+    right: u32,
+}
+
+impl<I> ShiftIter<I> where I: Iterator<Item = u8> {
+    fn new(mut bytes: I, left: u8, right: u8) -> ShiftIter<I> {
+        ShiftIter {
+            current: bytes.next().unwrap_or(0),
+            bytes,
+            left: left as u32,
+            right: right as u32,
+        }
+    }
+}
+
+impl<I> Iterator for ShiftIter<I> where I: Iterator<Item = u8> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.bytes.next()?;
+        let byte = self.current.checked_shl(self.left).unwrap_or(0) | next.checked_shr(self.right).unwrap_or(0);
+        self.current = next;
+        Some(byte)
+    }
+}
+
 pub struct Rotate90<'f, I> where I: DataBits {
     inner: &'f I,
 }
@@ -295,7 +450,7 @@ fn byte_and_bit_for_bit_index(bit: i16) -> (i16, u8) {
 mod test {
     use super::*;
     use bitcanvas::BitCanvas;
-    use bitcanvas::consts::{W8, H8};
+    use bitcanvas::consts::{W8, W16, H8};
 
     #[test]
     fn test_canvas_flip_h_and_offset_by_1() {
@@ -344,6 +499,44 @@ mod test {
         assert_eq!(rotate90.row_bytes(0, 0..1).next(), Some(0b0000_0001));
     }
 
+    #[test]
+    fn test_canvas_offset_bits() {
+        let mut canvas: BitCanvas<W16, H8> = BitCanvas::<W16, H8>::new(16, 8).unwrap();
+        canvas.row_mut(2).unwrap().copy_from_slice(&[0b1010_1100, 0b1000_0001]);
+
+        let right = canvas.offset_bits(3, 1);
+        let mut bytes = right.row_bytes(3, 0..3);
+        assert_eq!((bytes.next(), bytes.next(), bytes.next()), (Some(0b0001_0101), Some(0b1001_0000), Some(0b0010_0000)));
+        let mut bytes = right.row_bytes_rev(3, 0..3);
+        assert_eq!((bytes.next(), bytes.next(), bytes.next()), (Some(0b0000_0100), Some(0b0000_1001), Some(0b1010_1000)));
+
+        let left = canvas.offset_bits(-5, 0);
+        let mut bytes = left.row_bytes(2, -1..2);
+        assert_eq!((bytes.next(), bytes.next(), bytes.next()), (Some(0b0001_0101), Some(0b1001_0000), Some(0b0010_0000)));
+        assert_eq!(canvas.offset_bits(8, 0).row_bytes(2, 1..2).next(), Some(0b1010_1100));
+        assert_eq!(canvas.offset_bits(8, 0).row_bytes_rev(2, 1..2).next(), Some(0b0011_0101));
+    }
+
+    #[derive(Clone)]
+    struct Diagonal;
+
+    impl LitPixels for Diagonal {
+        fn lit(&self, x: i16, y: i16) -> bool {
+            x == y
+        }
+    }
+
+    #[test]
+    fn test_pixel_bits() {
+        let bits = PixelBits::new(Diagonal, 10);
+        let bytes = |mut iter: PixelBitsIter<Diagonal>| (iter.next(), iter.next(), iter.next());
+        assert_eq!(bytes(bits.row_bytes(1, 0..2)), (Some(0b0100_0000), Some(0), None));
+        assert_eq!(bytes(bits.row_bytes(9, 0..2)), (Some(0), Some(0b0100_0000), None));
+        assert_eq!(bytes(bits.row_bytes(12, 0..2)), (Some(0), Some(0), None));
+        assert_eq!(bytes(bits.row_bytes_rev(1, 0..2)), (Some(0), Some(0b0000_0010), None));
+        assert_eq!(bytes(bits.row_bytes(-1, -1..0)), (Some(0), None, None));
+    }
+
     #[test]
     fn check_positive_byte_and_bit() {
         assert_eq!((0, 0), byte_and_bit_for_bit_index(0));
@@ -0,0 +1,121 @@
+//! Estimated LED current and a budget limiter.
+//!
+//! The chip drives one matrix data register at a time, so every LED is lit for
+//! 1 / height of the scan, at the row current scaled by the PWM duty. Only LEDs of the
+//! matrices shown by the display mode are counted.
+
+use crate::{ConfigDisplayMode, ConfigMatrixMode, Configuration, Frame, LightingCurrent};
+
+/// Estimated average LED current in µA.
+pub fn estimate_microamps(lit: u32, matrix_mode: ConfigMatrixMode, current: LightingCurrent, pwm: u8) -> u32 {
+    lit * current.milliamps() as u32 * 1000 * pwm as u32 / (128 * matrix_mode.height() as u32)
+}
+
+/// Number of LEDs the frame lights with the configuration: only shown matrices and
+/// the pixels of the matrix mode are counted.
+pub fn lit_count(frame: &Frame, config: Configuration) -> u32 {
+    let matrix_mode = config.matrix_mode();
+    let mask = ((1u16 << matrix_mode.width()) - 1) as u8;
+    let count = |rows: &[u8]| -> u32 {
+        rows.iter()
+            .take(matrix_mode.height())
+            .map(|row| (row & mask).count_ones())
+            .sum()
+    };
+
+    match config.display_mode() {
+        ConfigDisplayMode::Matrix1Only => count(frame.matrix1()),
+        ConfigDisplayMode::Matrix2Only => count(frame.matrix2()),
+        ConfigDisplayMode::Matrix1and2 => count(frame.matrix1()) + count(frame.matrix2()),
+    }
+}
+
+/// Maximum estimated LED current.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CurrentLimit {
+    pub budget_milliamps: u16,
+}
+
+impl CurrentLimit {
+    pub fn new(budget_milliamps: u16) -> CurrentLimit {
+        CurrentLimit {
+            budget_milliamps,
+        }
+    }
+
+    /// Highest row current and PWM duty, not above the requested ones, that keep lit LEDs within budget.
+    ///
+    /// PWM is lowered first. If even the lowest duty is over budget, the row current is lowered too,
+    /// and when nothing fits the LEDs are turned off with duty 0.
+    pub fn fit(&self, lit: u32, matrix_mode: ConfigMatrixMode, current: LightingCurrent, pwm: u8) -> (LightingCurrent, u8) {
+        let budget = self.budget_milliamps as u32 * 1000;
+        if estimate_microamps(lit, matrix_mode, current, pwm) <= budget {
+            return (current, pwm);
+        }
+
+        let full = lit * current.milliamps() as u32 * 1000;
+        let max_pwm = budget * 128 * matrix_mode.height() as u32 / full;
+        if max_pwm >= 1 {
+            return (current, max_pwm as u8);
+        }
+
+        let lower = (1..current.milliamps() / 5).rev()
+            .filter_map(|step| LightingCurrent::from_milliamps(step * 5))
+            .find(|&lower| estimate_microamps(lit, matrix_mode, lower, 1) <= budget);
+        match lower {
+            Some(lower) => (lower, 1),
+            None => (LightingCurrent::Current5mA, 0),
+        }
+    }
+}
+
+/// Limit together with the row current and PWM duty requested by the caller.
+#[derive(Copy, Clone)]
+pub(crate) struct Limiter {
+    pub limit: CurrentLimit,
+    pub current: LightingCurrent,
+    pub pwm: u8,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn estimate() {
+        // 64 LEDs at 40 mA, each lit 1/8 of the time.
+        assert_eq!(estimate_microamps(64, ConfigMatrixMode::Size8x8, LightingCurrent::Current40mA, 128), 320_000);
+        assert_eq!(estimate_microamps(64, ConfigMatrixMode::Size8x8, LightingCurrent::Current40mA, 64), 160_000);
+        assert_eq!(estimate_microamps(0, ConfigMatrixMode::Size5x11, LightingCurrent::Current75mA, 128), 0);
+    }
+
+    #[test]
+    fn lit_count_follows_config() {
+        let mut frame = Frame::new();
+        frame.matrix1_mut()[0] = 0xff;
+        frame.matrix1_mut()[10] = 0xff;
+        frame.matrix2_mut()[1] = 0b0000_0011;
+
+        let mut config = Configuration::default();
+        assert_eq!(lit_count(&frame, config), 8);
+        config.set_display_mode(ConfigDisplayMode::Matrix1and2);
+        assert_eq!(lit_count(&frame, config), 10);
+        config.set_matrix_mode(ConfigMatrixMode::Size5x11);
+        assert_eq!(lit_count(&frame, config), 5 + 5 + 2);
+    }
+
+    #[test]
+    fn fit_lowers_pwm_then_current() {
+        let limit = CurrentLimit::new(100);
+        let mode = ConfigMatrixMode::Size8x8;
+
+        assert_eq!(limit.fit(16, mode, LightingCurrent::Current40mA, 128), (LightingCurrent::Current40mA, 128));
+        assert_eq!(limit.fit(64, mode, LightingCurrent::Current40mA, 128), (LightingCurrent::Current40mA, 40));
+        assert!(estimate_microamps(64, mode, LightingCurrent::Current40mA, 40) <= 100_000);
+
+        let limit = CurrentLimit::new(1);
+        assert_eq!(limit.fit(128, mode, LightingCurrent::Current75mA, 128), (LightingCurrent::Current5mA, 1));
+        let limit = CurrentLimit::new(0);
+        assert_eq!(limit.fit(1, mode, LightingCurrent::Current75mA, 128), (LightingCurrent::Current5mA, 0));
+    }
+}
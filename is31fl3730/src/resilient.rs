@@ -0,0 +1,358 @@
+use hal::i2c::{Error as _, ErrorKind};
+use crate::{Device, DeviceError};
+use crate::shadow::Dirty;
+
+/// Configuration sent to a device after every reset.
+pub trait Recipe<I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    fn apply(&mut self, device: &mut Device<I2C>) -> Result<(), DeviceError<I2C::Error>>;
+}
+
+impl<I2C, F> Recipe<I2C> for F
+    where
+        I2C: hal::i2c::I2c,
+        F: FnMut(&mut Device<I2C>) -> Result<(), DeviceError<I2C::Error>>,
+{
+    fn apply(&mut self, device: &mut Device<I2C>) -> Result<(), DeviceError<I2C::Error>> {
+        self(device)
+    }
+}
+
+/// How often to retry failed operations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Recoveries attempted right after an operation fails, before backing off.
+    pub retries: u8,
+    /// Wait before the first attempt after backing off, doubled after each failed attempt.
+    pub backoff_ms: u32,
+    /// Longest wait between attempts.
+    pub max_backoff_ms: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 1,
+            backoff_ms: 100,
+            max_backoff_ms: 5000,
+        }
+    }
+}
+
+/// Error counters.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Failed operations and recoveries.
+    pub errors: u32,
+    /// Successful recoveries.
+    pub recoveries: u32,
+    pub last_error: Option<ErrorKind>,
+}
+
+/// Device that recovers from bus errors.
+///
+/// After a bus error the chip is reset, configured with the recipe, and the shadowed
+/// state (configuration, lighting, PWM and both matrices) is sent again. If that keeps
+/// failing, the device backs off: operations return `DeviceError::Unavailable` without
+/// using the bus until the wait time passes with `tick`.
+///
+/// The example needs the simulator of the `std` feature.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use is31fl3730::{Address, Device, LightingCurrent, Resilient, RetryPolicy};
+/// use is31fl3730::sim::Chip;
+///
+/// let device = Device::new(Address::Address00, Chip::new(Address::Address00));
+/// let mut device = Resilient::new(device, RetryPolicy::default(), |d: &mut Device<Chip>| {
+///     d.modify_lighting(|l| l.set_current(LightingCurrent::Current20mA))
+/// });
+///
+/// device.run(|d| d.set_matrix1_rows(0, &[0xff])).unwrap();
+/// device.run(|d| d.update()).unwrap();
+/// assert_eq!(device.device().i2c().visible_matrix1().lit_count(), 8);
+/// ```
+pub struct Resilient<I2C, R>
+    where
+        I2C: hal::i2c::I2c,
+{
+    device: Device<I2C>,
+    recipe: R,
+    policy: RetryPolicy,
+    stats: Stats,
+    initialized: bool,
+    needs_recovery: bool,
+    wait_ms: u32,
+    next_backoff_ms: u32,
+}
+
+impl<I2C, R> Resilient<I2C, R>
+    where
+        I2C: hal::i2c::I2c,
+        R: Recipe<I2C>,
+{
+    /// Wrap device. It is reset and configured with the recipe before the first operation.
+    pub fn new(device: Device<I2C>, policy: RetryPolicy, recipe: R) -> Resilient<I2C, R> {
+        Resilient {
+            device,
+            recipe,
+            policy,
+            stats: Stats::default(),
+            initialized: false,
+            needs_recovery: true,
+            wait_ms: 0,
+            next_backoff_ms: policy.backoff_ms,
+        }
+    }
+
+    pub fn device(&self) -> &Device<I2C> {
+        &self.device
+    }
+
+    /// Release the wrapped device.
+    pub fn release(self) -> Device<I2C> {
+        self.device
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Check if operations are currently sent to the bus.
+    pub fn is_available(&self) -> bool {
+        self.wait_ms == 0
+    }
+
+    /// Advance the backoff timer.
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        self.wait_ms = self.wait_ms.saturating_sub(elapsed_ms);
+    }
+
+    /// Reset and restore the device on the next operation, for example, after a brown-out.
+    pub fn invalidate(&mut self) {
+        self.needs_recovery = true;
+    }
+
+    /// Run operation, recovering from bus errors according to the policy.
+    ///
+    /// Invalid arguments are returned right away and do not trigger recovery.
+    pub fn run<T, F>(&mut self, mut operation: F) -> Result<T, DeviceError<I2C::Error>>
+        where F: FnMut(&mut Device<I2C>) -> Result<T, DeviceError<I2C::Error>> {
+        if !self.is_available() {
+            return Err(DeviceError::Unavailable);
+        }
+
+        let mut attempt = 0;
+        loop {
+            let result = if self.needs_recovery {
+                self.recover().and_then(|_| operation(&mut self.device))
+            } else {
+                operation(&mut self.device)
+            };
+
+            let e = match result {
+                Ok(value) => {
+                    self.next_backoff_ms = self.policy.backoff_ms;
+                    return Ok(value);
+                },
+                Err(e) => e,
+            };
+
+            self.stats.errors = self.stats.errors.saturating_add(1);
+            self.stats.last_error = Some(e.kind());
+
+            if let DeviceError::Bus(_) = e {
+                self.needs_recovery = true;
+            } else {
+                return Err(e);
+            }
+
+            if attempt >= self.policy.retries {
+                self.wait_ms = self.next_backoff_ms.max(1);
+                self.next_backoff_ms = self.next_backoff_ms.saturating_mul(2).min(self.policy.max_backoff_ms);
+                return Err(e);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Reset the chip, apply the recipe and send the shadowed state.
+    ///
+    /// Once initialized, the whole shadow replaces what the recipe set, so settings changed
+    /// at runtime and the output requested from the current limit survive the reset.
+    fn recover(&mut self) -> Result<(), DeviceError<I2C::Error>> {
+        let previous = self.device.shadow;
+        let limiter = self.device.limiter;
+
+        self.device.reset()?;
+        self.recipe.apply(&mut self.device)?;
+
+        if self.initialized {
+            self.device.shadow = previous;
+            self.device.limiter = limiter;
+            self.device.shadow.dirty = Dirty::all();
+            self.device.flush()?;
+            self.stats.recoveries = self.stats.recoveries.saturating_add(1);
+        }
+
+        self.initialized = true;
+        self.needs_recovery = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigDisplayMode, CurrentLimit, Frame, LightingCurrent};
+    use crate::sim::{Chip, Error};
+    use hal::i2c::NoAcknowledgeSource;
+
+    fn setup(device: &mut Device<Chip>) -> Result<(), DeviceError<Error>> {
+        device.modify_lighting(|l| l.set_current(LightingCurrent::Current20mA))?;
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2))
+    }
+
+    type Setup = fn(&mut Device<Chip>) -> Result<(), DeviceError<Error>>;
+
+    fn resilient(chip: Address) -> Resilient<Chip, Setup> {
+        let device = Device::new(Address::Address00, Chip::new(chip));
+        Resilient::new(device, RetryPolicy { retries: 1, backoff_ms: 100, max_backoff_ms: 300 }, setup)
+    }
+
+    #[test]
+    fn first_operation_applies_recipe() {
+        let mut device = resilient(Address::Address00);
+        device.run(|d| d.set_pwm(10)).unwrap();
+
+        let chip = device.device().i2c();
+        assert_eq!(chip.lighting(), LightingCurrent::Current20mA as u8);
+        assert_eq!(chip.config(), ConfigDisplayMode::Matrix1and2 as u8);
+        assert_eq!(chip.pwm_duty(), 10);
+        assert_eq!(device.stats(), Stats::default());
+    }
+
+    #[test]
+    fn brown_out_is_recovered_on_bus_error() {
+        let mut device = resilient(Address::Address00);
+        device.run(|d| {
+            d.set_matrix2_rows(2, &[0xf0])?;
+            d.set_pwm(64)?;
+            d.shutdown()
+        }).unwrap();
+
+        // Chip drops off the bus and comes back with default registers.
+        device.device.i2c = Chip::new(Address::Address11);
+        assert!(device.run(|d| d.update()).is_err());
+        device.device.i2c = Chip::new(Address::Address00);
+        device.tick(100);
+        device.run(|d| d.update()).unwrap();
+
+        let chip = device.device().i2c();
+        assert!(chip.is_shutdown());
+        assert_eq!(chip.pwm_duty(), 64);
+        assert_eq!(chip.lighting(), LightingCurrent::Current20mA as u8);
+        assert_eq!(chip.matrix2_data()[2], 0xf0);
+        assert_eq!(device.stats().recoveries, 1);
+        assert_eq!(device.stats().errors, 2);
+    }
+
+    #[test]
+    fn invalidate_restores_frame() {
+        let mut device = resilient(Address::Address00);
+        device.run(|d| {
+            d.set_matrix1_rows(0, &[0xff, 0x81])?;
+            d.set_pwm(32)?;
+            d.update()
+        }).unwrap();
+
+        device.invalidate();
+        device.run(|d| d.set_matrix2_rows(0, &[0x01])).unwrap();
+        device.run(|d| d.update()).unwrap();
+
+        let chip = device.device().i2c();
+        assert_eq!(chip.visible_matrix1().lit_count(), 10);
+        assert_eq!(chip.visible_matrix2().lit_count(), 1);
+        assert_eq!(chip.pwm_duty(), 32);
+        assert_eq!(chip.lighting(), LightingCurrent::Current20mA as u8);
+        assert_eq!(device.stats().recoveries, 1);
+    }
+
+    #[test]
+    fn recovery_keeps_runtime_settings() {
+        let mut device = resilient(Address::Address00);
+        device.run(|d| {
+            d.modify_lighting(|l| l.set_current(LightingCurrent::Current40mA))?;
+            d.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix2Only))?;
+            d.set_matrix1_rows(0, &[0x0f])?;
+            d.update()
+        }).unwrap();
+
+        device.invalidate();
+        device.run(|d| d.update()).unwrap();
+
+        let chip = device.device().i2c();
+        assert_eq!(chip.lighting(), LightingCurrent::Current40mA as u8);
+        assert_eq!(chip.config(), ConfigDisplayMode::Matrix2Only as u8);
+        assert_eq!(device.device().lighting().current(), LightingCurrent::Current40mA);
+        assert_eq!(device.device().config().display_mode(), ConfigDisplayMode::Matrix2Only);
+        assert_eq!(chip.matrix1_data()[0], 0x0f);
+    }
+
+    #[test]
+    fn recovery_keeps_requested_output() {
+        let mut device = resilient(Address::Address00);
+        let mut frame = Frame::new();
+        frame.matrix1_mut()[0] = 0xff;
+        device.run(|d| {
+            d.set_current_limit(Some(CurrentLimit::new(10)));
+            d.set_pwm(128)?;
+            d.present(&frame)
+        }).unwrap();
+        let limited_pwm = device.device().i2c().pwm_duty();
+
+        device.invalidate();
+        device.run(|d| d.update()).unwrap();
+
+        assert_eq!(device.device().requested_output(), (LightingCurrent::Current20mA, 128));
+        assert_eq!(device.device().i2c().pwm_duty(), limited_pwm);
+        assert!(limited_pwm < 128);
+    }
+
+    #[test]
+    fn backs_off_while_failing() {
+        let mut device = resilient(Address::Address01);
+        let nack = DeviceError::Bus(Error::Nack(Address::Address00 as u8));
+
+        assert_eq!(device.run(|d| d.update()), Err(nack));
+        assert_eq!(device.stats().errors, 2);
+        assert_eq!(device.device().i2c().write_count(), 0);
+
+        assert!(!device.is_available());
+        assert_eq!(device.run(|d| d.update()), Err(DeviceError::Unavailable));
+        assert_eq!(device.stats().errors, 2);
+
+        device.tick(100);
+        assert_eq!(device.run(|d| d.update()), Err(nack));
+        assert_eq!(device.stats().errors, 4);
+
+        device.tick(100);
+        assert!(!device.is_available());
+        device.tick(100);
+        assert!(device.is_available());
+        assert_eq!(device.stats().last_error, Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)));
+    }
+
+    #[test]
+    fn invalid_arguments_do_not_recover() {
+        let mut device = resilient(Address::Address00);
+        device.run(|d| d.update()).unwrap();
+        let writes = device.device().i2c().write_count();
+
+        assert_eq!(device.run(|d| d.set_pwm(200)), Err(DeviceError::InvalidPwm(200)));
+        assert_eq!(device.device().i2c().write_count(), writes);
+        assert!(device.is_available());
+    }
+}
@@ -0,0 +1,300 @@
+//! Bus scan and hot-plug detection.
+//!
+//! The chip can not be read, so it is probed with an address-only write: a chip
+//! that acknowledges its address is present. The bus implementation must support
+//! empty writes.
+
+use hal::i2c::{Error, ErrorKind};
+use crate::{Address, Configuration, Device, DeviceError};
+
+/// All addresses the chip can be configured with.
+pub const ADDRESSES: [Address; 4] = [
+    Address::Address00,
+    Address::Address01,
+    Address::Address10,
+    Address::Address11,
+];
+
+/// Check if a chip acknowledges the address.
+pub fn probe<I2C>(i2c: &mut I2C, address: Address) -> Result<bool, I2C::Error>
+    where
+        I2C: hal::i2c::I2c
+{
+    match i2c.write(address as u8, &[]) {
+        Ok(()) => Ok(true),
+        Err(e) => match e.kind() {
+            ErrorKind::NoAcknowledge(_) => Ok(false),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Probe all addresses.
+pub fn scan<I2C>(i2c: &mut I2C) -> Result<AddressSet, I2C::Error>
+    where
+        I2C: hal::i2c::I2c
+{
+    let mut found = AddressSet::new();
+    for &address in ADDRESSES.iter() {
+        if probe(i2c, address)? {
+            found.insert(address);
+        }
+    }
+    Ok(found)
+}
+
+/// Set of chip addresses.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AddressSet(u8);
+
+impl AddressSet {
+    pub fn new() -> AddressSet {
+        AddressSet(0)
+    }
+
+    pub fn contains(&self, address: Address) -> bool {
+        self.0 & bit(address) != 0
+    }
+
+    pub fn insert(&mut self, address: Address) {
+        self.0 |= bit(address);
+    }
+
+    pub fn remove(&mut self, address: Address) {
+        self.0 &= !bit(address);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Addresses in the set, in `ADDRESSES` order.
+    pub fn iter(&self) -> impl Iterator<Item = Address> {
+        let set = *self;
+        ADDRESSES.iter().cloned().filter(move |&address| set.contains(address))
+    }
+}
+
+/// Addresses differ in the two lowest bits.
+fn bit(address: Address) -> u8 {
+    1 << (address as u8 & 0b11)
+}
+
+/// Change reported by `Monitor`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Chip acknowledged its address and was initialised.
+    Appeared(Address),
+    /// Chip stopped acknowledging its address.
+    Disappeared(Address),
+}
+
+/// Periodically probes all addresses, initialises chips that appear and reports changes.
+///
+/// Chips are initialised through a temporary `Device`: they are reset and configured.
+/// A `Device` kept elsewhere for a chip that appeared again should be invalidated
+/// or created anew.
+pub struct Monitor {
+    config: Configuration,
+    interval_ms: u32,
+    elapsed_ms: u32,
+    present: AddressSet,
+    scanned: bool,
+}
+
+impl Monitor {
+    /// Monitor that probes every `interval_ms` and configures new chips with config.
+    pub fn new(config: Configuration, interval_ms: u32) -> Monitor {
+        Monitor {
+            config,
+            interval_ms,
+            elapsed_ms: 0,
+            present: AddressSet::new(),
+            scanned: false,
+        }
+    }
+
+    /// Chips found by the last scan.
+    pub fn present(&self) -> AddressSet {
+        self.present
+    }
+
+    /// Advance time and scan if the interval passed. The first poll always scans.
+    pub fn poll<I2C, F>(&mut self, i2c: &mut I2C, elapsed_ms: u32, on_event: F) -> Result<(), DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c,
+            F: FnMut(Event)
+    {
+        self.elapsed_ms = self.elapsed_ms.saturating_add(elapsed_ms);
+        if self.scanned && self.elapsed_ms < self.interval_ms {
+            return Ok(());
+        }
+        self.rescan(i2c, on_event)
+    }
+
+    /// Probe all addresses now.
+    ///
+    /// A bus error other than a missing acknowledge keeps the state of that address and
+    /// does not stop the scan: a chip that fails to initialise is not reported and is tried
+    /// again by the next scan. Returns the first error.
+    pub fn rescan<I2C, F>(&mut self, i2c: &mut I2C, mut on_event: F) -> Result<(), DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c,
+            F: FnMut(Event)
+    {
+        self.elapsed_ms = 0;
+        self.scanned = true;
+
+        let mut result = Ok(());
+        for &address in ADDRESSES.iter() {
+            let found = probe(i2c, address)
+                .map_err(DeviceError::Bus)
+                .and_then(|found| Ok(found && self.init(i2c, address)?));
+            let found = match found {
+                Ok(found) => found,
+                Err(error) => {
+                    if result.is_ok() {
+                        result = Err(error);
+                    }
+                    continue;
+                },
+            };
+
+            match (self.present.contains(address), found) {
+                (false, true) => {
+                    self.present.insert(address);
+                    on_event(Event::Appeared(address));
+                },
+                (true, false) => {
+                    self.present.remove(address);
+                    on_event(Event::Disappeared(address));
+                },
+                _ => (),
+            }
+        }
+
+        result
+    }
+
+    /// Initialise a chip that was not present. Returns false if it went away meanwhile.
+    fn init<I2C>(&self, i2c: &mut I2C, address: Address) -> Result<bool, DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c
+    {
+        if self.present.contains(address) {
+            return Ok(true);
+        }
+
+        let config = self.config;
+        let mut device = Device::new(address, &mut *i2c);
+        let result = device.reset()
+            .and_then(|_| device.modify_config(|c| {
+                *c = config;
+                c
+            }));
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(DeviceError::Bus(e)) => match e.kind() {
+                ErrorKind::NoAcknowledge(_) => Ok(false),
+                _ => Err(DeviceError::Bus(e)),
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ConfigDisplayMode, ConfigMatrixMode};
+    use crate::sim::{self, Bus, Chip};
+    use hal::i2c::Operation;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    /// Bus that fails every transaction to one address with an error other than a missing acknowledge.
+    struct FailingBus {
+        bus: Bus,
+        failing: Address,
+    }
+
+    impl hal::i2c::ErrorType for FailingBus {
+        type Error = sim::Error;
+    }
+
+    impl hal::i2c::I2c for FailingBus {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), sim::Error> {
+            if address == self.failing as u8 {
+                return Err(sim::Error::InvalidRegister(0xff));
+            }
+            self.bus.transaction(address, operations)
+        }
+    }
+
+    #[test]
+    fn scan_finds_plugged_chips() {
+        let mut bus = Bus::new();
+        assert!(scan(&mut bus).unwrap().is_empty());
+
+        bus.plug(Chip::new(Address::Address11));
+        bus.plug(Chip::new(Address::Address01));
+        let found = scan(&mut bus).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found.contains(Address::Address01));
+        assert!(!found.contains(Address::Address00));
+        assert_eq!(found.iter().collect::<Vec<_>>(), [Address::Address01, Address::Address11]);
+        assert_eq!(probe(&mut bus, Address::Address10), Ok(false));
+    }
+
+    #[test]
+    fn monitor_reports_and_initialises() {
+        let mut config = Configuration::default();
+        config
+            .set_matrix_mode(ConfigMatrixMode::Size5x11)
+            .set_display_mode(ConfigDisplayMode::Matrix1and2);
+        let mut monitor = Monitor::new(config, 500);
+        let mut bus = Bus::new();
+        bus.plug(Chip::new(Address::Address10));
+
+        let mut events = Vec::new();
+        monitor.poll(&mut bus, 0, |event| events.push(event)).unwrap();
+        assert_eq!(events, [Event::Appeared(Address::Address10)]);
+        assert_eq!(bus.chip(Address::Address10).unwrap().decoded_config(), Ok(config));
+
+        bus.plug(Chip::new(Address::Address00));
+        bus.unplug(Address::Address10);
+        events.clear();
+        monitor.poll(&mut bus, 499, |event| events.push(event)).unwrap();
+        assert!(events.is_empty());
+
+        monitor.poll(&mut bus, 1, |event| events.push(event)).unwrap();
+        assert_eq!(events, [Event::Appeared(Address::Address00), Event::Disappeared(Address::Address10)]);
+        assert_eq!(monitor.present().iter().collect::<Vec<_>>(), [Address::Address00]);
+
+        // Present chips are not initialised again.
+        let writes = bus.chip(Address::Address00).unwrap().write_count();
+        monitor.rescan(&mut bus, |_| panic!()).unwrap();
+        assert_eq!(bus.chip(Address::Address00).unwrap().write_count(), writes + 1);
+    }
+
+    #[test]
+    fn rescan_continues_past_bus_errors() {
+        let mut monitor = Monitor::new(Configuration::default(), 500);
+        let mut bus = FailingBus { bus: Bus::new(), failing: Address::Address01 };
+        bus.bus.plug(Chip::new(Address::Address00));
+        bus.bus.plug(Chip::new(Address::Address01));
+        bus.bus.plug(Chip::new(Address::Address11));
+
+        let mut events = Vec::new();
+        let result = monitor.rescan(&mut bus, |event| events.push(event));
+        assert_eq!(result, Err(DeviceError::Bus(sim::Error::InvalidRegister(0xff))));
+        assert_eq!(events, [Event::Appeared(Address::Address00), Event::Appeared(Address::Address11)]);
+        assert_eq!(monitor.present().len(), 2);
+    }
+}
@@ -0,0 +1,297 @@
+//! Frame sequences: pre-drawn frames shown one after another for fixed durations.
+//!
+//! Like `animation`, time only advances with `tick`, and the device is written only
+//! when the shown frame changes.
+
+use crate::{ConfigMatrixMode, Device, DeviceError, Frame};
+use crate::pixels::DataBits;
+
+/// Contents of a sequence step.
+pub trait Picture {
+    /// Frame to present in the matrix mode.
+    fn frame(&self, matrix_mode: ConfigMatrixMode) -> Frame;
+}
+
+/// Packed rows, shown as they are.
+impl Picture for Frame {
+    fn frame(&self, _matrix_mode: ConfigMatrixMode) -> Frame {
+        *self
+    }
+}
+
+/// Pixels, packed with `Frame::from_data`.
+impl<DATA> Picture for &DATA where DATA: DataBits {
+    fn frame(&self, matrix_mode: ConfigMatrixMode) -> Frame {
+        Frame::from_data(matrix_mode, *self)
+    }
+}
+
+/// A picture and how long it is shown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step<P> {
+    pub picture: P,
+    /// Shown for at least 1 ms.
+    pub duration_ms: u32,
+}
+
+/// What happens after the last step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Repeat {
+    /// Stay on the last step.
+    Once,
+    /// Start over from the first step.
+    Loop,
+    /// Go back to the first step, then forward again.
+    PingPong,
+}
+
+/// Steps through a list of pictures.
+///
+/// ```
+/// use is31fl3730::Frame;
+/// use is31fl3730::sequence::{Player, Repeat, Step};
+///
+/// static SPINNER: [Step<Frame>; 2] = [
+///     Step { picture: Frame::from_rows([0b1000_0000; 11], [0; 11]), duration_ms: 100 },
+///     Step { picture: Frame::from_rows([0b0000_0001; 11], [0; 11]), duration_ms: 50 },
+/// ];
+///
+/// let mut player = Player::new(&SPINNER, Repeat::Loop);
+/// assert_eq!(player.tick(0), Some(0));
+/// assert_eq!(player.tick(99), None);
+/// assert_eq!(player.tick(1), Some(1));
+/// assert_eq!(player.tick(50), Some(0));
+/// ```
+pub struct Player<'a, P> where P: Picture {
+    steps: &'a [Step<P>],
+    repeat: Repeat,
+    index: usize,
+    backwards: bool,
+    /// Time since the start of the current step.
+    elapsed_ms: u32,
+    finished: bool,
+    /// Step last returned by tick.
+    shown: Option<usize>,
+}
+
+impl<'a, P> Player<'a, P> where P: Picture {
+    pub fn new(steps: &'a [Step<P>], repeat: Repeat) -> Player<'a, P> {
+        Player {
+            steps,
+            repeat,
+            index: 0,
+            backwards: false,
+            elapsed_ms: 0,
+            finished: false,
+            shown: None,
+        }
+    }
+
+    pub fn steps(&self) -> &'a [Step<P>] {
+        self.steps
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    /// Index of the current step.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Check if a `Repeat::Once` sequence reached its last step.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Start over from the first step. The device is written only if the shown frame changes.
+    pub fn restart(&mut self) {
+        self.index = 0;
+        self.backwards = false;
+        self.elapsed_ms = 0;
+        self.finished = false;
+    }
+
+    /// Advance time and return the index of the step to show if it changed.
+    pub fn tick(&mut self, elapsed_ms: u32) -> Option<usize> {
+        if self.steps.is_empty() {
+            return None;
+        }
+
+        self.elapsed_ms = self.elapsed_ms.saturating_add(elapsed_ms);
+        if let Some(cycle_ms) = self.cycle_ms() {
+            // A whole cycle ends on the same step, in the same direction.
+            self.elapsed_ms %= cycle_ms;
+        }
+
+        while !self.finished && self.elapsed_ms >= self.duration_ms(self.index) {
+            self.elapsed_ms -= self.duration_ms(self.index);
+            self.advance();
+        }
+
+        if self.shown == Some(self.index) {
+            return None;
+        }
+
+        self.shown = Some(self.index);
+        Some(self.index)
+    }
+
+    /// Advance time and present the step to device if it changed.
+    pub fn run<I2C, E>(&mut self, device: &mut Device<I2C>, elapsed_ms: u32) -> Result<(), DeviceError<E>>
+        where
+            I2C: hal::i2c::I2c<Error = E>
+    {
+        if let Some(index) = self.tick(elapsed_ms) {
+            let frame = self.steps[index].picture.frame(device.config().matrix_mode());
+            if let Err(e) = device.present(&frame) {
+                self.shown = None;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn duration_ms(&self, index: usize) -> u32 {
+        self.steps[index].duration_ms.max(1)
+    }
+
+    /// Time after which the player repeats itself, `None` if it does not.
+    fn cycle_ms(&self) -> Option<u32> {
+        let total = (0..self.steps.len())
+            .fold(0u32, |total, index| total.saturating_add(self.duration_ms(index)));
+        let last = self.steps.len() - 1;
+
+        match self.repeat {
+            Repeat::Once => None,
+            Repeat::Loop => Some(total),
+            Repeat::PingPong if last == 0 => Some(total),
+            Repeat::PingPong => Some(total
+                .saturating_mul(2)
+                .saturating_sub(self.duration_ms(0) + self.duration_ms(last))),
+        }
+    }
+
+    fn advance(&mut self) {
+        let last = self.steps.len() - 1;
+
+        match self.repeat {
+            Repeat::Once if self.index == last => self.finished = true,
+            Repeat::Once => self.index += 1,
+            Repeat::Loop => self.index = if self.index == last { 0 } else { self.index + 1 },
+            Repeat::PingPong if last == 0 => (),
+            Repeat::PingPong => {
+                if self.index == last {
+                    self.backwards = true;
+                } else if self.index == 0 {
+                    self.backwards = false;
+                }
+                self.index = if self.backwards { self.index - 1 } else { self.index + 1 };
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigDisplayMode};
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W16, H8};
+
+    fn steps(durations: &[u32]) -> [Step<Frame>; 4] {
+        let mut steps = [Step { picture: Frame::new(), duration_ms: 0 }; 4];
+        for (index, step) in steps.iter_mut().enumerate() {
+            step.picture.matrix1_mut()[0] = 1 << index;
+            step.duration_ms = durations[index];
+        }
+        steps
+    }
+
+    /// Steps shown by eight ticks.
+    fn shown<P: Picture>(player: &mut Player<P>, elapsed_ms: u32) -> [usize; 8] {
+        let mut shown = [0; 8];
+        for index in shown.iter_mut() {
+            player.tick(elapsed_ms);
+            *index = player.index();
+        }
+        shown
+    }
+
+    #[test]
+    fn repeat_modes() {
+        let steps = steps(&[10, 10, 10, 10]);
+
+        let mut player = Player::new(&steps, Repeat::Loop);
+        assert_eq!(shown(&mut player, 10), [1, 2, 3, 0, 1, 2, 3, 0]);
+
+        let mut player = Player::new(&steps, Repeat::PingPong);
+        assert_eq!(shown(&mut player, 10), [1, 2, 3, 2, 1, 0, 1, 2]);
+
+        let mut player = Player::new(&steps, Repeat::Once);
+        assert_eq!(shown(&mut player, 10), [1, 2, 3, 3, 3, 3, 3, 3]);
+        assert!(player.is_finished());
+        player.restart();
+        assert_eq!((player.index(), player.is_finished()), (0, false));
+    }
+
+    #[test]
+    fn durations_are_per_step() {
+        let steps = steps(&[30, 0, 20, 10]);
+
+        let mut player = Player::new(&steps, Repeat::Loop);
+        assert_eq!(player.tick(0), Some(0));
+        assert_eq!(player.tick(29), None);
+        assert_eq!(player.tick(1), Some(1));
+        assert_eq!(player.tick(1), Some(2));
+        assert_eq!(player.tick(25), Some(3));
+        // Skips whole cycles of 61 ms.
+        assert_eq!(player.tick(61 * 1000 + 5), Some(0));
+
+        let mut player = Player::new(&steps, Repeat::PingPong);
+        player.tick(30 + 1 + 20 + 10 + 20);
+        assert_eq!(player.index(), 1);
+        player.tick(1);
+        assert_eq!(player.index(), 0);
+        player.tick(u32::MAX);
+        assert!(player.index() < 4);
+    }
+
+    #[test]
+    fn run_presents_only_changes() {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+
+        let mut left = BitCanvas::<W16, H8>::new(16, 8).unwrap();
+        left.row_mut(0).unwrap().copy_from_slice(&[0b1000_0000, 0]);
+        let mut right = BitCanvas::<W16, H8>::new(16, 8).unwrap();
+        right.row_mut(0).unwrap().copy_from_slice(&[0, 0b0000_0001]);
+        let steps = [
+            Step { picture: &left, duration_ms: 100 },
+            Step { picture: &right, duration_ms: 100 },
+        ];
+
+        let mut player = Player::new(&steps, Repeat::Loop);
+        player.run(&mut device, 0).unwrap();
+        let writes = device.i2c().write_count();
+        assert!(device.i2c().visible_matrix1().pixel(0, 0));
+
+        for _ in 0..9 {
+            player.run(&mut device, 10).unwrap();
+        }
+        assert_eq!(device.i2c().write_count(), writes);
+
+        player.run(&mut device, 10).unwrap();
+        assert!(device.i2c().visible_matrix2().pixel(7, 0));
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+    }
+
+    #[test]
+    fn empty_sequence_does_nothing() {
+        let steps: [Step<Frame>; 0] = [];
+        let mut player = Player::new(&steps, Repeat::PingPong);
+        assert_eq!(player.tick(100), None);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{Configuration, Lighting};
+
+pub const MATRIX_ROWS: usize = 11;
+
+/// Copy of every writable chip register.
+#[derive(Copy, Clone)]
+pub struct Shadow {
+    pub config: Configuration,
+    pub lighting: Lighting,
+    pub pwm: u8,
+    pub matrix1: [u8; MATRIX_ROWS],
+    pub matrix2: [u8; MATRIX_ROWS],
+    pub dirty: Dirty,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Shadow {
+            config: Configuration::default(),
+            lighting: Lighting::default(),
+            pwm: 0b1000_0000,
+            matrix1: [0; MATRIX_ROWS],
+            matrix2: [0; MATRIX_ROWS],
+            dirty: Dirty::default(),
+        }
+    }
+}
+
+/// Registers modified in the shadow but not yet sent to the chip.
+#[derive(Copy, Clone, Default)]
+pub struct Dirty {
+    pub config: bool,
+    pub lighting: bool,
+    pub pwm: bool,
+    pub matrix1: Rows,
+    pub matrix2: Rows,
+    /// Matrix data was sent, but not latched with update.
+    pub update: bool,
+}
+
+impl Dirty {
+    pub fn all() -> Dirty {
+        Dirty {
+            config: true,
+            lighting: true,
+            pwm: true,
+            matrix1: Rows::all(),
+            matrix2: Rows::all(),
+            update: true,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.config || self.lighting || self.pwm
+            || !self.matrix1.is_empty() || !self.matrix2.is_empty()
+            || self.update
+    }
+}
+
+/// Set of matrix rows.
+#[derive(Copy, Clone, Default)]
+pub struct Rows {
+    bits: u16,
+}
+
+impl Rows {
+    pub fn all() -> Rows {
+        Rows { bits: (1 << MATRIX_ROWS) - 1 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn insert(&mut self, row: usize) {
+        self.bits |= 1 << row;
+    }
+
+    pub fn remove_range(&mut self, start: usize, end: usize) {
+        for row in start..end.min(MATRIX_ROWS) {
+            self.bits &= !(1 << row);
+        }
+    }
+
+    /// Smallest contiguous range covering all rows in the set.
+    pub fn span(&self) -> Option<(usize, usize)> {
+        if self.bits == 0 {
+            return None;
+        }
+
+        let start = self.bits.trailing_zeros() as usize;
+        let end = 16 - self.bits.leading_zeros() as usize;
+        Some((start, end))
+    }
+}
+
+/// Copy rows already sent to the chip into the shadow.
+pub fn copy_rows(shadow: &mut [u8; MATRIX_ROWS], start_row: usize, rows: &[u8]) {
+    for (row, value) in (start_row..MATRIX_ROWS).zip(rows) {
+        shadow[row] = *value;
+    }
+}
+
+/// Copy rows into the shadow, marking changed rows as dirty.
+pub fn buffer_rows(shadow: &mut [u8; MATRIX_ROWS], dirty: &mut Rows, start_row: usize, rows: &[u8]) {
+    for (row, value) in (start_row..MATRIX_ROWS).zip(rows) {
+        if shadow[row] != *value {
+            shadow[row] = *value;
+            dirty.insert(row);
+        }
+    }
+}
@@ -0,0 +1,518 @@
+//! Register-level simulator of a single IS31FL3730 chip.
+//!
+//! The simulator decodes every write the same way the chip does: the first byte
+//! selects a register, following bytes are written to auto-incremented register
+//! addresses. Matrix data only becomes visible after a write to the
+//! `UpdateColumn` register.
+
+use hal::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+use crate::register::Register;
+use crate::configuration::ConfigMask;
+use core::convert::TryFrom;
+use crate::{Address, Configuration, InvalidEncoding, Lighting};
+use hal;
+
+extern crate std;
+use std::vec::Vec;
+
+const DATA_LEN: usize = 11;
+
+/// Simulated chip error.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    /// Nobody acknowledged the address.
+    Nack(u8),
+    /// Write to the register the chip does not have.
+    InvalidRegister(u8),
+}
+
+/// Simulated IS31FL3730 chip on the I2C bus.
+pub struct Chip {
+    address: u8,
+    config: u8,
+    lighting: u8,
+    pwm: u8,
+    matrix1: [u8; DATA_LEN],
+    matrix2: [u8; DATA_LEN],
+    latched1: [u8; DATA_LEN],
+    latched2: [u8; DATA_LEN],
+    writes: usize,
+    bytes: usize,
+}
+
+impl Chip {
+    pub fn new(address: Address) -> Chip {
+        Chip {
+            address: address as u8,
+            config: 0,
+            lighting: 0,
+            pwm: 0b1000_0000,
+            matrix1: [0; DATA_LEN],
+            matrix2: [0; DATA_LEN],
+            latched1: [0; DATA_LEN],
+            latched2: [0; DATA_LEN],
+            writes: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Raw value of the configuration register.
+    pub fn config(&self) -> u8 {
+        self.config
+    }
+
+    /// Raw value of the lighting effect register.
+    pub fn lighting(&self) -> u8 {
+        self.lighting
+    }
+
+    /// Decoded value of the configuration register.
+    pub fn decoded_config(&self) -> Result<Configuration, InvalidEncoding> {
+        Configuration::try_from(self.config)
+    }
+
+    /// Decoded value of the lighting effect register.
+    pub fn decoded_lighting(&self) -> Result<Lighting, InvalidEncoding> {
+        Lighting::try_from(self.lighting)
+    }
+
+    /// Raw value of the PWM register.
+    pub fn pwm(&self) -> u8 {
+        self.pwm
+    }
+
+    /// PWM duty in 1/128 steps (0 - 128).
+    pub fn pwm_duty(&self) -> u8 {
+        if self.pwm & 0b1000_0000 > 0 { 128 } else { self.pwm }
+    }
+
+    /// Matrix 1 data registers, written but not necessarily latched.
+    pub fn matrix1_data(&self) -> &[u8] {
+        &self.matrix1
+    }
+
+    /// Matrix 2 data registers, written but not necessarily latched.
+    pub fn matrix2_data(&self) -> &[u8] {
+        &self.matrix2
+    }
+
+    /// True if the software shutdown bit is set.
+    pub fn is_shutdown(&self) -> bool {
+        self.config & ConfigMask::SoftwareShutdown as u8 > 0
+    }
+
+    /// True if the audio input modulates the intensity.
+    pub fn is_audio_enabled(&self) -> bool {
+        self.config & ConfigMask::Audio as u8 > 0
+    }
+
+    /// Number of pixels in a row and number of rows for the configured matrix mode.
+    pub fn matrix_size(&self) -> (usize, usize) {
+        match self.config & ConfigMask::MatrixMode as u8 {
+            0b00 => (8, 8),
+            0b01 => (7, 9),
+            0b10 => (6, 10),
+            _ => (5, 11),
+        }
+    }
+
+    /// What is currently lit on the first matrix.
+    pub fn visible_matrix1(&self) -> MatrixView {
+        let shown = self.config & ConfigMask::DisplayMode as u8 != 0b0000_1000;
+        self.view(&self.latched1, shown)
+    }
+
+    /// What is currently lit on the second matrix.
+    pub fn visible_matrix2(&self) -> MatrixView {
+        let shown = self.config & ConfigMask::DisplayMode as u8 != 0b0000_0000;
+        self.view(&self.latched2, shown)
+    }
+
+    /// Number of write transactions addressed to this chip.
+    pub fn write_count(&self) -> usize {
+        self.writes
+    }
+
+    /// Number of bytes (including register addresses) written to this chip.
+    pub fn byte_count(&self) -> usize {
+        self.bytes
+    }
+
+    fn view(&self, latched: &[u8; DATA_LEN], shown: bool) -> MatrixView {
+        let (width, height) = self.matrix_size();
+        let mut rows = [0; DATA_LEN];
+
+        if shown && !self.is_shutdown() {
+            let mask = (0xffu16 >> (8 - width)) as u8;
+            for (row, value) in rows.iter_mut().zip(latched.iter()).take(height) {
+                *row = value & mask;
+            }
+        }
+
+        MatrixView {
+            rows,
+            width,
+            height,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.config = 0;
+        self.lighting = 0;
+        self.pwm = 0b1000_0000;
+        self.matrix1 = [0; DATA_LEN];
+        self.matrix2 = [0; DATA_LEN];
+        self.latched1 = [0; DATA_LEN];
+        self.latched2 = [0; DATA_LEN];
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        const MATRIX1_BEGIN: u8 = Register::Matrix1Begin as u8;
+        const MATRIX1_END: u8 = Register::Matrix1End as u8;
+        const MATRIX2_BEGIN: u8 = Register::Matrix2Begin as u8;
+        const MATRIX2_END: u8 = Register::Matrix2End as u8;
+        const CONFIG: u8 = Register::Config as u8;
+        const UPDATE_COLUMN: u8 = Register::UpdateColumn as u8;
+        const LIGHTING_EFFECT: u8 = Register::LightingEffect as u8;
+        const PWM: u8 = Register::Pwm as u8;
+        const RESET: u8 = Register::Reset as u8;
+
+        match register {
+            CONFIG => self.config = value,
+            MATRIX1_BEGIN..=MATRIX1_END => self.matrix1[(register - MATRIX1_BEGIN) as usize] = value,
+            UPDATE_COLUMN => {
+                self.latched1 = self.matrix1;
+                self.latched2 = self.matrix2;
+            },
+            LIGHTING_EFFECT => self.lighting = value,
+            MATRIX2_BEGIN..=MATRIX2_END => self.matrix2[(register - MATRIX2_BEGIN) as usize] = value,
+            PWM => self.pwm = value,
+            RESET => self.reset(),
+            other => return Err(Error::InvalidRegister(other)),
+        }
+
+        Ok(())
+    }
+
+    /// Decode a single write transfer.
+    fn transfer(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        if address != self.address {
+            return Err(Error::Nack(address));
+        }
+
+        self.writes += 1;
+        self.bytes += bytes.len();
+
+        // Address-only writes probe the bus and do not select a register.
+        let (register, data) = match bytes.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+
+        let mut register = *register;
+        for value in data {
+            self.write_register(register, *value)?;
+            register = register.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    /// Consecutive write operations are sent without a repeated start, so they are decoded
+    /// as a single transfer. The chip can not be read.
+    fn operations(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        let mut writing = false;
+        for operation in operations {
+            match operation {
+                Operation::Write(chunk) => {
+                    bytes.extend_from_slice(chunk);
+                    writing = true;
+                },
+                Operation::Read(_) => {
+                    if writing {
+                        self.transfer(address, &bytes)?;
+                    }
+                    return Err(Error::Nack(address));
+                },
+            }
+        }
+
+        if writing {
+            self.transfer(address, &bytes)?;
+        }
+        Ok(())
+    }
+}
+
+impl hal::i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match *self {
+            Error::Nack(_) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            Error::InvalidRegister(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl hal::i2c::ErrorType for Chip {
+    type Error = Error;
+}
+
+impl hal::i2c::I2c for Chip {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.operations(address, operations)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for Chip {
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.operations(address, operations)
+    }
+}
+
+/// Simulated bus with a chip on any of the four addresses, which can be plugged in and out.
+pub struct Bus {
+    chips: [Option<Chip>; 4],
+}
+
+impl Bus {
+    pub fn new() -> Bus {
+        Bus {
+            chips: [None, None, None, None],
+        }
+    }
+
+    /// Connect chip, returns the chip it replaced at the same address.
+    pub fn plug(&mut self, chip: Chip) -> Option<Chip> {
+        let slot = slot(chip.address);
+        self.chips[slot].replace(chip)
+    }
+
+    /// Disconnect chip at address.
+    pub fn unplug(&mut self, address: Address) -> Option<Chip> {
+        self.chips[slot(address as u8)].take()
+    }
+
+    pub fn chip(&self, address: Address) -> Option<&Chip> {
+        self.chips[slot(address as u8)].as_ref()
+    }
+
+    fn operations(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        match self.chips.iter_mut().flatten().find(|chip| chip.address == address) {
+            Some(chip) => chip.operations(address, operations),
+            None => Err(Error::Nack(address)),
+        }
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Bus::new()
+    }
+}
+
+/// Addresses differ in the two lowest bits.
+fn slot(address: u8) -> usize {
+    (address & 0b11) as usize
+}
+
+impl hal::i2c::ErrorType for Bus {
+    type Error = Error;
+}
+
+impl hal::i2c::I2c for Bus {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.operations(address, operations)
+    }
+}
+
+/// Lit pixels of a single matrix.
+///
+/// Rows are reported as in the data registers: the leftmost pixel is the highest
+/// bit used by the matrix mode, e.g. bit 7 in 8x8 mode and bit 4 in 5x11 mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MatrixView {
+    rows: [u8; DATA_LEN],
+    width: usize,
+    height: usize,
+}
+
+impl MatrixView {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Visible rows, masked to the bits used by the matrix mode.
+    pub fn rows(&self) -> &[u8] {
+        &self.rows[..self.height]
+    }
+
+    /// Check if pixel at (x, y) is lit.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        self.rows[y] & (1 << (self.width - 1 - x)) > 0
+    }
+
+    /// Number of lit pixels.
+    pub fn lit_count(&self) -> u32 {
+        self.rows().iter().map(|r| r.count_ones()).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Device, ConfigDisplayMode, ConfigMatrixMode};
+    use crate::display::{OutputRows, MatrixTargetPrimary8x8, MatrixTargetSecondary8x8};
+    use crate::pixels::DataBits;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W8, H8};
+
+    fn device() -> Device<Chip> {
+        Device::new(Address::Address11, Chip::new(Address::Address11))
+    }
+
+    #[test]
+    fn data_is_visible_only_after_update() {
+        let mut device = device();
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        device.set_matrix1_rows(2, &[0b1010_0000, 0b0000_0101]).unwrap();
+
+        assert_eq!(&device.i2c().matrix1_data()[..4], &[0, 0, 0b1010_0000, 0b0000_0101]);
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+
+        device.update().unwrap();
+
+        let visible = device.i2c().visible_matrix1();
+        assert_eq!(visible.rows(), &[0, 0, 0b1010_0000, 0b0000_0101, 0, 0, 0, 0]);
+        assert!(visible.pixel(0, 2));
+        assert!(visible.pixel(7, 3));
+        assert!(!visible.pixel(1, 2));
+    }
+
+    #[test]
+    fn display_mode_hides_matrix() {
+        let mut device = device();
+        device.set_matrix1_rows(0, &[0xff]).unwrap();
+        device.set_matrix2_rows(0, &[0xff]).unwrap();
+        device.update().unwrap();
+
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 8);
+        assert_eq!(device.i2c().visible_matrix2().lit_count(), 0);
+
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix2Only)).unwrap();
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+        assert_eq!(device.i2c().visible_matrix2().lit_count(), 8);
+    }
+
+    #[test]
+    fn software_shutdown_blanks_both_matrices() {
+        use hal::i2c::I2c;
+
+        let mut chip = Chip::new(Address::Address00);
+        let address = Address::Address00 as u8;
+        chip.write(address, &[Register::Config as u8, 0b0001_1000]).unwrap();
+        chip.write(address, &[Register::Matrix1Begin as u8, 0xff]).unwrap();
+        chip.write(address, &[Register::Matrix2Begin as u8, 0xff]).unwrap();
+        chip.write(address, &[Register::UpdateColumn as u8, 0]).unwrap();
+        assert_eq!(chip.visible_matrix1().lit_count() + chip.visible_matrix2().lit_count(), 16);
+
+        chip.write(address, &[Register::Config as u8, 0b1001_1000]).unwrap();
+        assert!(chip.is_shutdown());
+        assert_eq!(chip.visible_matrix1().lit_count() + chip.visible_matrix2().lit_count(), 0);
+        assert_eq!(chip.matrix1_data()[0], 0xff);
+    }
+
+    #[test]
+    fn matrix_mode_masks_rows() {
+        let mut device = device();
+        device.modify_config(|c| c.set_matrix_mode(ConfigMatrixMode::Size5x11)).unwrap();
+        device.set_matrix1_rows(0, &[0xff; 11]).unwrap();
+        device.update().unwrap();
+
+        let visible = device.i2c().visible_matrix1();
+        assert_eq!((visible.width(), visible.height()), (5, 11));
+        assert_eq!(visible.rows(), &[0b1_1111; 11]);
+    }
+
+    #[test]
+    fn writes_auto_increment_into_neighbour_registers() {
+        let mut chip = Chip::new(Address::Address00);
+        hal::i2c::I2c::write(&mut chip, Address::Address00 as u8, &[0x0b, 0xff, 0x00, 0b0000_0111]).unwrap();
+
+        assert_eq!(chip.matrix1_data()[10], 0xff);
+        assert_eq!(chip.lighting(), 0b0000_0111);
+        assert_eq!(chip.visible_matrix1().lit_count(), 0);
+    }
+
+    #[test]
+    fn consecutive_writes_form_one_transfer() {
+        use hal::i2c::{I2c, Operation};
+
+        let mut chip = Chip::new(Address::Address00);
+        let mut operations = [Operation::Write(&[0x01]), Operation::Write(&[0xff, 0x81])];
+        chip.transaction(Address::Address00 as u8, &mut operations).unwrap();
+
+        assert_eq!(&chip.matrix1_data()[..2], &[0xff, 0x81]);
+        assert_eq!(chip.write_count(), 1);
+        assert_eq!(chip.byte_count(), 3);
+    }
+
+    #[test]
+    fn decodes_registers() {
+        let mut chip = Chip::new(Address::Address00);
+        hal::i2c::I2c::write(&mut chip, Address::Address00 as u8, &[0x00, 0b0000_1011]).unwrap();
+        assert_eq!(chip.decoded_config().unwrap().display_mode(), ConfigDisplayMode::Matrix2Only);
+        assert_eq!(chip.decoded_config().unwrap().matrix_mode(), ConfigMatrixMode::Size5x11);
+
+        hal::i2c::I2c::write(&mut chip, Address::Address00 as u8, &[0x0d, 0b0000_1111]).unwrap();
+        assert_eq!(chip.decoded_lighting(), Err(InvalidEncoding(0b0000_1111)));
+    }
+
+    #[test]
+    fn reset_restores_defaults() {
+        let mut device = device();
+        device.set_pwm(3).unwrap();
+        device.set_matrix1_rows(0, &[0xff]).unwrap();
+        device.update().unwrap();
+        device.reset().unwrap();
+
+        assert_eq!(device.i2c().pwm(), 0b1000_0000);
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+        assert_eq!(device.i2c().write_count(), 4);
+    }
+
+    #[test]
+    fn wrong_address_is_not_acknowledged() {
+        let mut device = Device::new(Address::Address01, Chip::new(Address::Address11));
+        assert_eq!(device.update(), Err(crate::DeviceError::Bus(Error::Nack(Address::Address01 as u8))));
+    }
+
+    #[test]
+    fn output_rows_render_canvas() {
+        let mut canvas: BitCanvas<W8, H8> = BitCanvas::<W8, H8>::new(8, 8).unwrap();
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1100_0000]);
+        canvas.row_mut(7).unwrap().copy_from_slice(&[0b0000_0001]);
+
+        let mut device = device();
+        device.modify_config(|c| c.set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        MatrixTargetPrimary8x8 {}.output_pixels(&mut device, &canvas).unwrap();
+        MatrixTargetSecondary8x8 {}.output_pixels(&mut device, &canvas.flip_h().offset_bytes(1, 0)).unwrap();
+        device.update().unwrap();
+
+        let m1 = device.i2c().visible_matrix1();
+        assert!(m1.pixel(0, 0) && m1.pixel(1, 0) && m1.pixel(7, 7));
+        assert_eq!(m1.lit_count(), 3);
+
+        let m2 = device.i2c().visible_matrix2();
+        assert!(m2.pixel(7, 0) && m2.pixel(6, 0) && m2.pixel(0, 7));
+        assert_eq!(m2.lit_count(), 3);
+    }
+}
@@ -0,0 +1,140 @@
+use core::ops::{Deref, DerefMut};
+use crate::{Device, DeviceError};
+
+/// Keeps device in software shutdown while alive.
+///
+/// Gives access to the device, so that the next frame can be buffered while asleep.
+/// Dropping the guard wakes the device and ignores errors, call `wake` to handle them.
+pub struct ShutdownGuard<'a, I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    device: &'a mut Device<I2C>,
+    woken: bool,
+}
+
+impl<'a, I2C, E> ShutdownGuard<'a, I2C>
+    where
+        I2C: hal::i2c::I2c<Error = E>,
+{
+    pub(crate) fn new(device: &'a mut Device<I2C>) -> ShutdownGuard<'a, I2C> {
+        ShutdownGuard {
+            device,
+            woken: false,
+        }
+    }
+
+    /// Leave software shutdown and flush changes buffered while asleep.
+    pub fn wake(mut self) -> Result<(), DeviceError<E>> {
+        self.woken = true;
+        self.device.wake()
+    }
+}
+
+impl<'a, I2C> Deref for ShutdownGuard<'a, I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    type Target = Device<I2C>;
+
+    fn deref(&self) -> &Device<I2C> {
+        self.device
+    }
+}
+
+impl<'a, I2C> DerefMut for ShutdownGuard<'a, I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    fn deref_mut(&mut self) -> &mut Device<I2C> {
+        self.device
+    }
+}
+
+impl<'a, I2C> Drop for ShutdownGuard<'a, I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    fn drop(&mut self) {
+        if !self.woken {
+            let _ = self.device.wake();
+        }
+    }
+}
+
+/// What to do with the display after advancing the idle timer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IdleAction {
+    None,
+    /// Content did not change for too long, enter software shutdown.
+    Blank,
+    /// Content changed while blanked, leave software shutdown.
+    Wake,
+}
+
+/// Policy that blanks the display after its content stays unchanged for a while.
+///
+/// ```
+/// use is31fl3730::{IdleBlank, IdleAction};
+///
+/// let mut idle = IdleBlank::new(2);
+/// assert_eq!(idle.tick(1500, false), IdleAction::None);
+/// assert_eq!(idle.tick(500, false), IdleAction::Blank);
+/// assert_eq!(idle.tick(500, false), IdleAction::None);
+/// assert_eq!(idle.tick(100, true), IdleAction::Wake);
+/// ```
+#[derive(Copy, Clone)]
+pub struct IdleBlank {
+    timeout_ms: u32,
+    idle_ms: u32,
+    blanked: bool,
+}
+
+impl IdleBlank {
+    pub fn new(timeout_secs: u16) -> IdleBlank {
+        IdleBlank {
+            timeout_ms: timeout_secs as u32 * 1000,
+            idle_ms: 0,
+            blanked: false,
+        }
+    }
+
+    /// Check if the display should currently be blank.
+    pub fn is_blanked(&self) -> bool {
+        self.blanked
+    }
+
+    /// Advance the timer by elapsed time. `changed` tells if the content changed since the last tick.
+    pub fn tick(&mut self, elapsed_ms: u32, changed: bool) -> IdleAction {
+        if changed {
+            self.idle_ms = 0;
+            if self.blanked {
+                self.blanked = false;
+                return IdleAction::Wake;
+            }
+            return IdleAction::None;
+        }
+
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+        if !self.blanked && self.idle_ms >= self.timeout_ms {
+            self.blanked = true;
+            return IdleAction::Blank;
+        }
+
+        IdleAction::None
+    }
+
+    /// Advance the timer and shut down or wake the device as needed.
+    pub fn apply<I2C, E>(&mut self, device: &mut Device<I2C>, elapsed_ms: u32, changed: bool) -> Result<IdleAction, DeviceError<E>>
+        where
+            I2C: hal::i2c::I2c<Error = E>
+    {
+        let action = self.tick(elapsed_ms, changed);
+        match action {
+            IdleAction::Blank => device.shutdown()?,
+            IdleAction::Wake => device.wake()?,
+            IdleAction::None => (),
+        }
+        Ok(action)
+    }
+}
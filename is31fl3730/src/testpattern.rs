@@ -0,0 +1,314 @@
+//! Test patterns and a stepped LED self-test for bring-up.
+//!
+//! Patterns are sized for an `OutputRows` target and shown with `Pattern::output`.
+//! `SelfTest` walks through all of them for one matrix, either step by step with
+//! `show` and `advance`, or at once with `run` and a confirmation callback.
+
+use crate::{Device, DeviceError};
+use crate::display::OutputRows;
+use crate::pixels::{LitPixels, PixelBits};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pattern {
+    AllOn,
+    /// Top-left LED lit.
+    Checkerboard,
+    /// Top-left LED off.
+    InverseCheckerboard,
+    /// Single lit row.
+    Row(u8),
+    /// Single lit column.
+    Column(u8),
+    /// Single lit LED at column and row.
+    Led(u8, u8),
+    /// Top row and left column lit to show the orientation, plus matrix index + 1
+    /// LEDs on the bottom row.
+    Identify(u8),
+}
+
+impl Pattern {
+    /// Is the LED lit in a matrix of width x height.
+    pub fn lit(&self, x: u8, y: u8, width: u8, height: u8) -> bool {
+        if x >= width || y >= height {
+            return false;
+        }
+
+        match *self {
+            Pattern::AllOn => true,
+            Pattern::Checkerboard => (x as u16 + y as u16) & 1 == 0,
+            Pattern::InverseCheckerboard => (x as u16 + y as u16) & 1 == 1,
+            Pattern::Row(row) => y == row,
+            Pattern::Column(column) => x == column,
+            Pattern::Led(column, row) => x == column && y == row,
+            Pattern::Identify(index) => x == 0 || y == 0 || (y == height - 1 && x <= index),
+        }
+    }
+
+    /// Pattern sized for the target.
+    pub fn bits<T>(&self, _target: &T) -> PatternBits
+        where
+            T: OutputRows
+    {
+        let sized = SizedPattern {
+            pattern: *self,
+            width: T::WIDTH as u8,
+            height: T::HEIGHT as u8,
+        };
+        PixelBits::new(sized, T::WIDTH as i16)
+    }
+
+    /// Show the pattern on the matrix of target.
+    pub fn output<T, I2C>(&self, target: &T, device: &mut Device<I2C>) -> Result<(), DeviceError<I2C::Error>>
+        where
+            T: OutputRows,
+            I2C: hal::i2c::I2c
+    {
+        target.output_pixels(device, &self.bits(target))?;
+        device.update()
+    }
+}
+
+/// Pattern as `DataBits`.
+pub type PatternBits = PixelBits<SizedPattern>;
+
+/// Pattern for a matrix of width x height.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SizedPattern {
+    pattern: Pattern,
+    width: u8,
+    height: u8,
+}
+
+impl LitPixels for SizedPattern {
+    fn lit(&self, x: i16, y: i16) -> bool {
+        x >= 0 && y >= 0 && x < 256 && y < 256
+            && self.pattern.lit(x as u8, y as u8, self.width, self.height)
+    }
+}
+
+/// Answer to a self-test step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Pass,
+    Fail,
+    /// Stop the self-test.
+    Abort,
+}
+
+/// Outcome of `SelfTest::run`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub passed: u16,
+    pub failed: u16,
+    pub first_failure: Option<Pattern>,
+    pub aborted: bool,
+}
+
+/// Self-test of a single matrix: all-on, both checkerboards, row and column sweeps,
+/// single LED walk and identification.
+///
+/// The device must be configured with the matrix mode of the target and a display mode
+/// that shows its matrix.
+pub struct SelfTest<T>
+    where
+        T: OutputRows
+{
+    target: T,
+    index: u8,
+    step: u16,
+}
+
+impl<T> SelfTest<T>
+    where
+        T: OutputRows
+{
+    /// Self-test of target, identified as matrix `index`.
+    pub fn new(target: T, index: u8) -> SelfTest<T> {
+        SelfTest {
+            target,
+            index,
+            step: 0,
+        }
+    }
+
+    /// Number of steps.
+    pub fn steps(&self) -> u16 {
+        let (width, height) = (T::WIDTH as u16, T::HEIGHT as u16);
+        3 + height + width + width * height + 1
+    }
+
+    /// Index of the current step.
+    pub fn step(&self) -> u16 {
+        self.step
+    }
+
+    /// Pattern of the current step, `None` after the last step.
+    pub fn pattern(&self) -> Option<Pattern> {
+        let (width, height) = (T::WIDTH as u16, T::HEIGHT as u16);
+        let mut step = self.step;
+
+        let fixed = [Pattern::AllOn, Pattern::Checkerboard, Pattern::InverseCheckerboard];
+        if let Some(pattern) = fixed.get(step as usize) {
+            return Some(*pattern);
+        }
+        step -= fixed.len() as u16;
+
+        if step < height {
+            return Some(Pattern::Row(step as u8));
+        }
+        step -= height;
+
+        if step < width {
+            return Some(Pattern::Column(step as u8));
+        }
+        step -= width;
+
+        if step < width * height {
+            return Some(Pattern::Led((step % width) as u8, (step / width) as u8));
+        }
+        step -= width * height;
+
+        if step == 0 {
+            return Some(Pattern::Identify(self.index));
+        }
+        None
+    }
+
+    /// Show the current step.
+    pub fn show<I2C>(&self, device: &mut Device<I2C>) -> Result<(), DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c
+    {
+        match self.pattern() {
+            Some(pattern) => pattern.output(&self.target, device),
+            None => Ok(()),
+        }
+    }
+
+    /// Move to the next step. Returns its pattern, `None` if the self-test is done.
+    pub fn advance(&mut self) -> Option<Pattern> {
+        if self.step < self.steps() {
+            self.step += 1;
+        }
+        self.pattern()
+    }
+
+    /// Start over from the first step.
+    pub fn restart(&mut self) {
+        self.step = 0;
+    }
+
+    /// Show the remaining steps one by one and ask confirm about each.
+    pub fn run<I2C, F>(&mut self, device: &mut Device<I2C>, mut confirm: F) -> Result<Report, DeviceError<I2C::Error>>
+        where
+            I2C: hal::i2c::I2c,
+            F: FnMut(&Pattern) -> Verdict
+    {
+        let mut report = Report::default();
+
+        while let Some(pattern) = self.pattern() {
+            self.show(device)?;
+            match confirm(&pattern) {
+                Verdict::Pass => report.passed += 1,
+                Verdict::Fail => {
+                    report.failed += 1;
+                    report.first_failure = report.first_failure.or(Some(pattern));
+                },
+                Verdict::Abort => {
+                    report.aborted = true;
+                    break;
+                },
+            }
+            self.advance();
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigDisplayMode, ConfigMatrixMode};
+    use crate::display::{MatrixTargetSecondary5x11, MatrixTargetPrimary8x8};
+    use crate::sim::Chip;
+
+    fn device(matrix_mode: ConfigMatrixMode) -> Device<Chip> {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.modify_config(|c| c
+            .set_matrix_mode(matrix_mode)
+            .set_display_mode(ConfigDisplayMode::Matrix1and2)).unwrap();
+        device
+    }
+
+    #[test]
+    fn patterns_light_expected_leds() {
+        let mut device = device(ConfigMatrixMode::Size5x11);
+        let target = MatrixTargetSecondary5x11 {};
+        let lit = |pattern: Pattern, device: &mut Device<Chip>| {
+            pattern.output(&target, device).unwrap();
+            device.i2c().visible_matrix2().lit_count()
+        };
+
+        assert_eq!(lit(Pattern::AllOn, &mut device), 55);
+        assert_eq!(lit(Pattern::Checkerboard, &mut device), 28);
+        assert_eq!(lit(Pattern::InverseCheckerboard, &mut device), 27);
+        assert_eq!(lit(Pattern::Row(10), &mut device), 5);
+        assert_eq!(lit(Pattern::Column(4), &mut device), 11);
+        assert_eq!(lit(Pattern::Led(4, 10), &mut device), 1);
+        assert!(device.i2c().visible_matrix2().pixel(4, 10));
+        assert_eq!(lit(Pattern::Identify(2), &mut device), 5 + 10 + 2);
+        assert!(device.i2c().visible_matrix2().pixel(2, 10));
+        assert_eq!(device.i2c().visible_matrix1().lit_count(), 0);
+    }
+
+    #[test]
+    fn wide_checkerboard() {
+        assert!(Pattern::Checkerboard.lit(200, 100, 255, 255));
+        assert!(Pattern::InverseCheckerboard.lit(200, 101, 255, 255));
+        assert!(!Pattern::Checkerboard.lit(254, 1, 255, 255));
+    }
+
+    #[test]
+    fn self_test_walks_every_led() {
+        let mut device = device(ConfigMatrixMode::Size8x8);
+        let mut test = SelfTest::new(MatrixTargetPrimary8x8 {}, 0);
+        assert_eq!(test.steps(), 3 + 8 + 8 + 64 + 1);
+
+        let mut walked = [[false; 8]; 8];
+        while let Some(pattern) = test.pattern() {
+            test.show(&mut device).unwrap();
+            if let Pattern::Led(x, y) = pattern {
+                let visible = device.i2c().visible_matrix1();
+                assert_eq!(visible.lit_count(), 1);
+                assert!(visible.pixel(x as usize, y as usize));
+                walked[y as usize][x as usize] = true;
+            }
+            test.advance();
+        }
+        assert_eq!(test.step(), test.steps());
+        assert!(walked.iter().all(|row| row.iter().all(|&led| led)));
+        assert_eq!(test.advance(), None);
+    }
+
+    #[test]
+    fn run_reports_failures_and_aborts() {
+        let mut device = device(ConfigMatrixMode::Size8x8);
+        let mut test = SelfTest::new(MatrixTargetPrimary8x8 {}, 1);
+
+        let report = test.run(&mut device, |pattern| match pattern {
+            Pattern::Column(3) | Pattern::Led(..) => Verdict::Fail,
+            _ => Verdict::Pass,
+        }).unwrap();
+        assert_eq!(report, Report { passed: 19, failed: 65, first_failure: Some(Pattern::Column(3)), aborted: false });
+        assert!(device.i2c().visible_matrix1().pixel(1, 7));
+
+        test.restart();
+        let report = test.run(&mut device, |pattern| match pattern {
+            Pattern::Row(_) => Verdict::Abort,
+            _ => Verdict::Pass,
+        }).unwrap();
+        assert_eq!((report.passed, report.aborted), (3, true));
+        assert_eq!(test.pattern(), Some(Pattern::Row(0)));
+    }
+}
@@ -0,0 +1,321 @@
+//! Recorded bus transactions, for comparing rendering output against golden files.
+//!
+//! A transcript is stored as text, one write per line: the address, a colon and the
+//! bytes, all in hex. Blank lines and lines starting with `#` are ignored.
+//!
+//! ```text
+//! # Matrix 1 rows 0 - 1, then update.
+//! 63: 01 80 40
+//! 63: 0c 00
+//! ```
+
+extern crate std;
+
+use core::fmt;
+use std::format;
+use std::string::String;
+use std::vec::Vec;
+use hal::i2c::Operation;
+use crate::register::Register;
+
+/// A single write.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub address: u8,
+    pub bytes: Vec<u8>,
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}:", self.address)?;
+        for byte in &self.bytes {
+            write!(f, " {:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Line of transcript text that could not be parsed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// Line number, starting at 1.
+    pub line: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid transaction on line {}", self.line)
+    }
+}
+
+/// Writes in the order they were sent.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transcript {
+    transactions: Vec<Transaction>,
+}
+
+impl Transcript {
+    pub fn new() -> Transcript {
+        Transcript::default()
+    }
+
+    pub fn push(&mut self, address: u8, bytes: &[u8]) {
+        self.transactions.push(Transaction {
+            address,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+    }
+
+    /// Read transcript text.
+    pub fn parse(text: &str) -> Result<Transcript, ParseError> {
+        let mut transcript = Transcript::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = ParseError { line: index + 1 };
+            let mut parts = line.splitn(2, ':');
+            let address = parts.next()
+                .and_then(|address| u8::from_str_radix(address.trim(), 16).ok())
+                .ok_or(error)?;
+            let bytes = parts.next()
+                .ok_or(error)?
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| error))
+                .collect::<Result<Vec<u8>, ParseError>>()?;
+
+            transcript.transactions.push(Transaction { address, bytes });
+        }
+
+        Ok(transcript)
+    }
+
+    /// Describe how this transcript differs from the expected one, register by register.
+    /// Returns `None` if they are the same.
+    pub fn diff(&self, expected: &Transcript) -> Option<String> {
+        if self == expected {
+            return None;
+        }
+
+        let mut diff = format!(
+            "transcripts differ, expected {} transactions, got {}\n",
+            expected.transactions.len(),
+            self.transactions.len(),
+        );
+        let len = self.transactions.len().max(expected.transactions.len());
+
+        for index in 0..len {
+            match (expected.transactions.get(index), self.transactions.get(index)) {
+                (Some(expected), Some(actual)) if expected == actual => (),
+                (Some(expected), Some(actual)) if same_registers(expected, actual) => {
+                    let register = expected.bytes[0];
+                    let values = expected.bytes[1..].iter().zip(actual.bytes[1..].iter());
+                    for (offset, (expected_value, actual_value)) in values.enumerate() {
+                        if expected_value != actual_value {
+                            diff += &format!(
+                                "#{} {:02x} {}: expected {:08b}, got {:08b}\n",
+                                index,
+                                actual.address,
+                                register_name(register.wrapping_add(offset as u8)),
+                                expected_value,
+                                actual_value,
+                            );
+                        }
+                    }
+                },
+                (Some(expected), Some(actual)) => {
+                    diff += &format!("#{} expected {}\n", index, expected);
+                    diff += &format!("#{} got      {}\n", index, actual);
+                },
+                (Some(expected), None) => diff += &format!("#{} missing  {}\n", index, expected),
+                (None, Some(actual)) => diff += &format!("#{} extra    {}\n", index, actual),
+                (None, None) => (),
+            }
+        }
+
+        Some(diff)
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for transaction in &self.transactions {
+            writeln!(f, "{}", transaction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes to the same registers of the same chip.
+fn same_registers(expected: &Transaction, actual: &Transaction) -> bool {
+    expected.address == actual.address
+        && expected.bytes.len() == actual.bytes.len()
+        && !expected.bytes.is_empty()
+        && expected.bytes.first() == actual.bytes.first()
+}
+
+/// Register name, with the row for matrix data registers.
+fn register_name(register: u8) -> String {
+    const CONFIG: u8 = Register::Config as u8;
+    const MATRIX1_BEGIN: u8 = Register::Matrix1Begin as u8;
+    const MATRIX1_END: u8 = Register::Matrix1End as u8;
+    const UPDATE_COLUMN: u8 = Register::UpdateColumn as u8;
+    const LIGHTING_EFFECT: u8 = Register::LightingEffect as u8;
+    const MATRIX2_BEGIN: u8 = Register::Matrix2Begin as u8;
+    const MATRIX2_END: u8 = Register::Matrix2End as u8;
+    const PWM: u8 = Register::Pwm as u8;
+    const RESET: u8 = Register::Reset as u8;
+
+    match register {
+        CONFIG => "config".into(),
+        MATRIX1_BEGIN..=MATRIX1_END => format!("matrix1 row {}", register - MATRIX1_BEGIN),
+        UPDATE_COLUMN => "update".into(),
+        LIGHTING_EFFECT => "lighting".into(),
+        MATRIX2_BEGIN..=MATRIX2_END => format!("matrix2 row {}", register - MATRIX2_BEGIN),
+        PWM => "pwm".into(),
+        RESET => "reset".into(),
+        other => format!("register {:02x}", other),
+    }
+}
+
+/// Compare transcript with the golden file at path and panic with the difference.
+///
+/// When the `UPDATE_GOLDEN` environment variable is set, the golden file is written instead.
+pub fn assert_golden(path: &str, actual: &Transcript) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(path, format!("{}", actual)).expect("golden file can not be written");
+        return;
+    }
+
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("{}: {}, run with UPDATE_GOLDEN=1 to create it", path, e));
+    let expected = Transcript::parse(&text)
+        .unwrap_or_else(|e| panic!("{}: {}", path, e));
+
+    if let Some(diff) = actual.diff(&expected) {
+        panic!("{} does not match, run with UPDATE_GOLDEN=1 to accept the change\n{}", path, diff);
+    }
+}
+
+/// Bus wrapper that records every write before passing it on. Reads are passed on unrecorded.
+pub struct Recorder<I2C> {
+    i2c: I2C,
+    transcript: Transcript,
+}
+
+impl<I2C> Recorder<I2C>
+    where
+        I2C: hal::i2c::I2c
+{
+    pub fn new(i2c: I2C) -> Recorder<I2C> {
+        Recorder {
+            i2c,
+            transcript: Transcript::new(),
+        }
+    }
+
+    pub fn i2c(&self) -> &I2C {
+        &self.i2c
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    /// Take the recorded transcript and start a new one.
+    pub fn take_transcript(&mut self) -> Transcript {
+        core::mem::take(&mut self.transcript)
+    }
+
+    pub fn release(self) -> (I2C, Transcript) {
+        (self.i2c, self.transcript)
+    }
+}
+
+impl<I2C> hal::i2c::ErrorType for Recorder<I2C>
+    where
+        I2C: hal::i2c::I2c
+{
+    type Error = I2C::Error;
+}
+
+impl<I2C> hal::i2c::I2c for Recorder<I2C>
+    where
+        I2C: hal::i2c::I2c
+{
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        for operation in operations.iter() {
+            if let Operation::Write(bytes) = operation {
+                self.transcript.push(address, bytes);
+            }
+        }
+        self.i2c.transaction(address, operations)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Address, ConfigMatrixMode, Device};
+    use crate::display::MatrixBank;
+    use crate::layout::{Layout, Placement, Rotation};
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W16, H8};
+
+    #[test]
+    fn text_round_trip() {
+        let mut transcript = Transcript::new();
+        transcript.push(0x63, &[0x01, 0x80, 0x40]);
+        transcript.push(0x60, &[]);
+        let text = format!("{}", transcript);
+        assert_eq!(text, "63: 01 80 40\n60:\n");
+        assert_eq!(Transcript::parse(&format!("# comment\n\n{}", text)), Ok(transcript));
+        assert_eq!(Transcript::parse("63: 01\n63 01"), Err(ParseError { line: 2 }));
+        assert_eq!(Transcript::parse("63: 1x"), Err(ParseError { line: 1 }));
+    }
+
+    #[test]
+    fn diff_names_registers() {
+        let expected = Transcript::parse("63: 01 80 40\n63: 0c 00\n63: 19 80").unwrap();
+        assert_eq!(expected.diff(&expected), None);
+
+        let actual = Transcript::parse("63: 01 80 20\n63: 0d 00").unwrap();
+        assert_eq!(actual.diff(&expected).unwrap(), "\
+transcripts differ, expected 3 transactions, got 2
+#0 63 matrix1 row 1: expected 01000000, got 00100000
+#1 expected 63: 0c 00
+#1 got      63: 0d 00
+#2 missing  63: 19 80
+");
+    }
+
+    #[test]
+    fn layout_matches_golden() {
+        const PLACEMENTS: [Placement; 2] = [
+            Placement { address: Address::Address11, bank: MatrixBank::Matrix1, x: 0, y: 0, rotation: Rotation::Deg90, mirror: false },
+            Placement { address: Address::Address11, bank: MatrixBank::Matrix2, x: 8, y: 0, rotation: Rotation::Deg180, mirror: true },
+        ];
+        let layout = Layout { matrix_mode: ConfigMatrixMode::Size8x8, placements: &PLACEMENTS };
+
+        let mut canvas = BitCanvas::<W16, H8>::new(16, 8).unwrap();
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1100_0000, 0b0000_0011]);
+        canvas.row_mut(5).unwrap().copy_from_slice(&[0b0001_0000, 0b0100_0000]);
+
+        let mut device = Device::new(Address::Address11, Recorder::new(Chip::new(Address::Address11)));
+        layout.output_pixels(&mut device, &canvas).unwrap();
+
+        assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/golden/layout.txt"), device.i2c().transcript());
+    }
+}
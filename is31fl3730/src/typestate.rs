@@ -0,0 +1,304 @@
+//! Device that carries the configured matrix and display mode in its type.
+//!
+//! Outputting to a target of a different matrix size, or writing rows of a matrix
+//! that is not displayed, fails to compile:
+//!
+//! ```
+//! use embedded_hal as hal;
+//! use is31fl3730::display::MatrixTargetPrimary8x8;
+//! use is31fl3730::pixels::DataBits;
+//! use is31fl3730::typestate::{TypedDevice, Size8x8, Matrix1Only};
+//!
+//! fn draw<I2C, D>(device: &mut TypedDevice<I2C, Size8x8, Matrix1Only>, data: &D)
+//!     where I2C: hal::i2c::I2c, D: DataBits
+//! {
+//!     let _ = device.output(&MatrixTargetPrimary8x8 {}, data);
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! use embedded_hal as hal;
+//! use is31fl3730::display::MatrixTargetPrimary5x11;
+//! use is31fl3730::pixels::DataBits;
+//! use is31fl3730::typestate::{TypedDevice, Size8x8, Matrix1Only};
+//!
+//! fn draw<I2C, D>(device: &mut TypedDevice<I2C, Size8x8, Matrix1Only>, data: &D)
+//!     where I2C: hal::i2c::I2c, D: DataBits
+//! {
+//!     let _ = device.output(&MatrixTargetPrimary5x11 {}, data);
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! use embedded_hal as hal;
+//! use is31fl3730::typestate::{TypedDevice, Size8x8, Matrix1Only};
+//!
+//! fn draw<I2C>(device: &mut TypedDevice<I2C, Size8x8, Matrix1Only>)
+//!     where I2C: hal::i2c::I2c
+//! {
+//!     let _ = device.set_matrix2_rows(0, &[0xff]);
+//! }
+//! ```
+
+use core::marker::PhantomData;
+use crate::{Device, DeviceError, ConfigMatrixMode, ConfigDisplayMode, Lighting};
+use crate::display::*;
+use hal;
+
+/// Matrix mode known at compile time.
+pub trait MatrixMode {
+    const MODE: ConfigMatrixMode;
+}
+
+pub struct Size8x8;
+pub struct Size7x9;
+pub struct Size6x10;
+pub struct Size5x11;
+
+impl MatrixMode for Size8x8 {
+    const MODE: ConfigMatrixMode = ConfigMatrixMode::Size8x8;
+}
+
+impl MatrixMode for Size7x9 {
+    const MODE: ConfigMatrixMode = ConfigMatrixMode::Size7x9;
+}
+
+impl MatrixMode for Size6x10 {
+    const MODE: ConfigMatrixMode = ConfigMatrixMode::Size6x10;
+}
+
+impl MatrixMode for Size5x11 {
+    const MODE: ConfigMatrixMode = ConfigMatrixMode::Size5x11;
+}
+
+/// Display mode known at compile time.
+pub trait DisplayMode {
+    const MODE: ConfigDisplayMode;
+}
+
+pub struct Matrix1Only;
+pub struct Matrix2Only;
+pub struct Matrix1and2;
+
+impl DisplayMode for Matrix1Only {
+    const MODE: ConfigDisplayMode = ConfigDisplayMode::Matrix1Only;
+}
+
+impl DisplayMode for Matrix2Only {
+    const MODE: ConfigDisplayMode = ConfigDisplayMode::Matrix2Only;
+}
+
+impl DisplayMode for Matrix1and2 {
+    const MODE: ConfigDisplayMode = ConfigDisplayMode::Matrix1and2;
+}
+
+/// First matrix.
+pub struct Matrix1;
+/// Second matrix.
+pub struct Matrix2;
+
+/// Implemented by display modes that show the matrix `M`.
+pub trait Shows<M> {}
+
+impl Shows<Matrix1> for Matrix1Only {}
+impl Shows<Matrix1> for Matrix1and2 {}
+impl Shows<Matrix2> for Matrix2Only {}
+impl Shows<Matrix2> for Matrix1and2 {}
+
+/// Output target bound to a matrix mode and a matrix.
+pub trait Target: OutputRows {
+    type Size: MatrixMode;
+    type Matrix;
+}
+
+impl Target for MatrixTargetPrimary8x8 {
+    type Size = Size8x8;
+    type Matrix = Matrix1;
+}
+
+impl Target for MatrixTargetSecondary8x8 {
+    type Size = Size8x8;
+    type Matrix = Matrix2;
+}
+
+impl Target for MatrixTargetPrimary7x9 {
+    type Size = Size7x9;
+    type Matrix = Matrix1;
+}
+
+impl Target for MatrixTargetSecondary7x9 {
+    type Size = Size7x9;
+    type Matrix = Matrix2;
+}
+
+impl Target for MatrixTargetPrimary6x10 {
+    type Size = Size6x10;
+    type Matrix = Matrix1;
+}
+
+impl Target for MatrixTargetSecondary6x10 {
+    type Size = Size6x10;
+    type Matrix = Matrix2;
+}
+
+impl Target for MatrixTargetPrimary5x11 {
+    type Size = Size5x11;
+    type Matrix = Matrix1;
+}
+
+impl Target for MatrixTargetSecondary5x11 {
+    type Size = Size5x11;
+    type Matrix = Matrix2;
+}
+
+/// Result of configuring modes: on failure, the device is returned untyped.
+pub type Configured<I2C, M, D, E> = Result<TypedDevice<I2C, M, D>, (Device<I2C>, DeviceError<E>)>;
+
+/// Result of switching modes: on failure, the device is returned in the previous mode.
+pub type Switched<I2C, M, D, T, E> = Result<T, (TypedDevice<I2C, M, D>, DeviceError<E>)>;
+
+/// Device configured with matrix mode `M` and display mode `D`.
+pub struct TypedDevice<I2C, M, D>
+    where
+        I2C: hal::i2c::I2c,
+{
+    device: Device<I2C>,
+    _mode: PhantomData<(M, D)>,
+}
+
+impl<I2C, E, M, D> TypedDevice<I2C, M, D>
+    where
+        I2C: hal::i2c::I2c<Error = E>,
+        M: MatrixMode,
+        D: DisplayMode,
+{
+    /// Send matrix and display mode to device.
+    ///
+    /// On failure, the device is returned back together with the error.
+    pub fn configure(mut device: Device<I2C>) -> Configured<I2C, M, D, E> {
+        match device.modify_config(|c| c.set_matrix_mode(M::MODE).set_display_mode(D::MODE)) {
+            Ok(()) => Ok(TypedDevice {
+                device,
+                _mode: PhantomData,
+            }),
+            Err(e) => Err((device, e)),
+        }
+    }
+
+    /// Get the untyped device.
+    pub fn device(&self) -> &Device<I2C> {
+        &self.device
+    }
+
+    /// Release the untyped device.
+    pub fn release(self) -> Device<I2C> {
+        self.device
+    }
+
+    /// Switch to a different matrix mode.
+    pub fn into_matrix_mode<M2>(self) -> Switched<I2C, M, D, TypedDevice<I2C, M2, D>, E>
+        where
+            M2: MatrixMode
+    {
+        TypedDevice::configure(self.device)
+            .map_err(|(device, e)| (TypedDevice { device, _mode: PhantomData }, e))
+    }
+
+    /// Switch to a different display mode.
+    pub fn into_display_mode<D2>(self) -> Switched<I2C, M, D, TypedDevice<I2C, M, D2>, E>
+        where
+            D2: DisplayMode
+    {
+        TypedDevice::configure(self.device)
+            .map_err(|(device, e)| (TypedDevice { device, _mode: PhantomData }, e))
+    }
+
+    /// Output pixels to a target that matches the matrix mode and is displayed.
+    pub fn output<T, DATA>(&mut self, target: &T, data: &DATA) -> Result<(), DeviceError<E>>
+        where
+            T: Target<Size = M>,
+            D: Shows<T::Matrix>,
+            DATA: crate::pixels::DataBits
+    {
+        let mut buffer: [u8; 11] = [0; 11];
+        target.pack_rows(data, &mut buffer);
+        target.write_buffer(&mut self.device, &buffer[0..T::HEIGHT])
+    }
+
+    /// Write pixels for the first matrix. Call update to flush updates.
+    pub fn set_matrix1_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>>
+        where
+            D: Shows<Matrix1>
+    {
+        self.device.set_matrix1_rows(start_row, rows)
+    }
+
+    /// Write pixels for the second matrix. Call update to flush updates.
+    pub fn set_matrix2_rows(&mut self, start_row: u8, rows: &[u8]) -> Result<(), DeviceError<E>>
+        where
+            D: Shows<Matrix2>
+    {
+        self.device.set_matrix2_rows(start_row, rows)
+    }
+
+    /// Flush display updates.
+    pub fn update(&mut self) -> Result<(), DeviceError<E>> {
+        self.device.update()
+    }
+
+    /// Set PWM duty in 1/128 steps (0 - 128), 128 is full brightness.
+    pub fn set_pwm(&mut self, value: u8) -> Result<(), DeviceError<E>> {
+        self.device.set_pwm(value)
+    }
+
+    /// Modify lighting configuration and send it to device.
+    pub fn modify_lighting<F>(&mut self, modify: F) -> Result<(), DeviceError<E>>
+        where F: FnMut(&mut Lighting) -> &mut Lighting {
+        self.device.modify_lighting(modify)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Address;
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W8, H16};
+
+    #[test]
+    fn configure_and_switch_modes() {
+        let device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        let device: TypedDevice<_, Size8x8, Matrix1Only> = TypedDevice::configure(device).ok().unwrap();
+        assert_eq!(device.device().i2c().config(), 0b0000_0000);
+
+        let device: TypedDevice<_, Size5x11, Matrix1Only> = device.into_matrix_mode().ok().unwrap();
+        assert_eq!(device.device().i2c().config(), 0b0000_0011);
+
+        let device: TypedDevice<_, Size5x11, Matrix1and2> = device.into_display_mode().ok().unwrap();
+        assert_eq!(device.device().i2c().config(), 0b0001_1011);
+    }
+
+    #[test]
+    fn output_to_matching_target() {
+        let mut canvas = BitCanvas::<W8, H16>::new(8, 11).unwrap();
+        canvas.row_mut(10).unwrap().copy_from_slice(&[0b1000_0000]);
+
+        let device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        let mut device: TypedDevice<_, Size5x11, Matrix2Only> = TypedDevice::configure(device).ok().unwrap();
+        device.output(&MatrixTargetSecondary5x11 {}, &canvas).unwrap();
+        device.update().unwrap();
+
+        let visible = device.device().i2c().visible_matrix2();
+        assert!(visible.pixel(0, 10));
+        assert_eq!(visible.lit_count(), 1);
+    }
+
+    #[test]
+    fn failed_configuration_returns_device() {
+        let device = Device::new(Address::Address01, Chip::new(Address::Address00));
+        let result: Result<TypedDevice<_, Size7x9, Matrix1and2>, _> = TypedDevice::configure(device);
+        let (device, _) = result.err().unwrap();
+        assert!(device.config().matrix_mode() == ConfigMatrixMode::Size8x8);
+    }
+}
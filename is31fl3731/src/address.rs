@@ -0,0 +1,20 @@
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Address {
+    /// AD connected to GND.
+    ///
+    /// 11101 00
+    Address00 = 0b111_0100,
+    /// AD connected to VCC.
+    ///
+    /// 11101 11
+    Address11 = 0b111_0111,
+    /// AD connected to SCL.
+    ///
+    /// 11101 01
+    Address01 = 0b111_0101,
+    /// AD connected to SDA.
+    ///
+    /// 11101 10
+    Address10 = 0b111_0110,
+}
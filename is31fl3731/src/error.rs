@@ -0,0 +1,24 @@
+use hal::i2c::ErrorKind;
+
+/// Error returned by device operations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DeviceError<E> {
+    /// I2C bus error.
+    Bus(E),
+    /// Frame number above 7.
+    InvalidFrame(u8),
+    /// Setting does not fit into its register field.
+    InvalidSetting(u8),
+}
+
+impl<E> hal::i2c::Error for DeviceError<E>
+    where
+        E: hal::i2c::Error
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            DeviceError::Bus(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
@@ -0,0 +1,318 @@
+#![no_std]
+#![deny(trivial_casts)]
+#![deny(trivial_numeric_casts)]
+
+//! Driver for the IS31FL3731 charlieplexed matrix driver: 16 x 9 LEDs with 8 bit PWM each,
+//! eight frames, auto play and breathing.
+//!
+//! Pixels are numbered left to right, top to bottom. Columns 0 - 7 are matrix A,
+//! columns 8 - 15 are matrix B.
+
+extern crate embedded_hal as hal;
+
+mod address;
+mod register;
+mod error;
+mod play;
+#[cfg(any(test, feature = "std"))]
+pub mod sim;
+#[cfg(feature = "eh02")]
+pub use i2c_compat as eh02;
+
+pub use address::{Address};
+pub use error::{DeviceError};
+pub use play::{AutoPlay, Breath};
+pub use register::{Register};
+
+use is31fl3730::display::row_bits;
+use is31fl3730::grayscale::GrayPixels;
+use is31fl3730::pixels::DataBits;
+use register::{COMMAND, FUNCTION_PAGE, LED_CONTROL, LED_CONTROL_LEN, PWM, MODE_PICTURE, FRAME_MASK};
+
+/// Pixels in a row.
+pub const WIDTH: usize = 16;
+/// Number of rows.
+pub const HEIGHT: usize = 9;
+/// Number of frames.
+pub const FRAMES: u8 = 8;
+
+pub struct Device<I2C>
+    where
+        I2C: hal::i2c::I2c,
+{
+    address: Address,
+    i2c: I2C,
+    /// Page selected by the last command register write, unknown after bus errors.
+    page: Option<u8>,
+}
+
+impl<I2C, E> Device<I2C>
+    where
+        I2C: hal::i2c::I2c<Error = E>,
+{
+    pub fn new(address: Address, i2c: I2C) -> Device<I2C> {
+        Device {
+            address,
+            i2c,
+            page: None,
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Get the underlying bus.
+    pub fn i2c(&self) -> &I2C {
+        &self.i2c
+    }
+
+    /// Release the underlying bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Clear all frames, stop auto play and breathing, and show frame 0.
+    ///
+    /// The chip is kept in software shutdown while frames are cleared.
+    pub fn init(&mut self) -> Result<(), DeviceError<E>> {
+        self.shutdown()?;
+        for frame in 0..FRAMES {
+            self.clear_frame(frame)?;
+        }
+        self.write_function(Register::Breath2, &[0])?;
+        self.write_function(Register::DisplayOption, &[0])?;
+        self.write_function(Register::AudioSync, &[0])?;
+        self.show_frame(0)?;
+        self.wake()
+    }
+
+    /// Enter software shutdown. Frames are kept and can still be modified.
+    pub fn shutdown(&mut self) -> Result<(), DeviceError<E>> {
+        self.write_function(Register::Shutdown, &[0])
+    }
+
+    /// Leave software shutdown.
+    pub fn wake(&mut self) -> Result<(), DeviceError<E>> {
+        self.write_function(Register::Shutdown, &[1])
+    }
+
+    /// Show a single frame (0 - 7), stopping auto play.
+    pub fn show_frame(&mut self, frame: u8) -> Result<(), DeviceError<E>> {
+        check_frame(frame)?;
+        self.write_function(Register::Config, &[MODE_PICTURE])?;
+        self.write_function(Register::PictureDisplay, &[frame])
+    }
+
+    /// Play frames one after another.
+    pub fn set_auto_play(&mut self, play: AutoPlay) -> Result<(), DeviceError<E>> {
+        let [config, control1, control2] = play.registers()?;
+        self.write_function(Register::AutoPlay1, &[control1, control2])?;
+        self.write_function(Register::Config, &[config])
+    }
+
+    /// Breathe the shown frames, `None` stops breathing.
+    pub fn set_breath(&mut self, breath: Option<Breath>) -> Result<(), DeviceError<E>> {
+        match breath {
+            Some(breath) => {
+                let registers = breath.registers()?;
+                self.write_function(Register::Breath1, &registers)
+            },
+            None => self.write_function(Register::Breath2, &[0]),
+        }
+    }
+
+    /// Frame currently shown by the chip.
+    pub fn current_frame(&mut self) -> Result<u8, DeviceError<E>> {
+        let mut state = [0];
+        self.select(FUNCTION_PAGE)?;
+        self.i2c.write_read(self.address as u8, &[Register::FrameState as u8], &mut state)
+            .map_err(|e| self.bus_error(e))?;
+        Ok(state[0] & FRAME_MASK)
+    }
+
+    /// Turn off all LEDs of frame.
+    pub fn clear_frame(&mut self, frame: u8) -> Result<(), DeviceError<E>> {
+        self.write_frame(frame, &[0; LED_CONTROL_LEN], |_, _| 0)
+    }
+
+    /// Output the top-left `WIDTH` x `HEIGHT` pixels of data to frame, lit pixels with PWM duty (0 - 255).
+    pub fn output_pixels<DATA>(&mut self, frame: u8, data: &DATA, pwm: u8) -> Result<(), DeviceError<E>>
+        where
+            DATA: DataBits
+    {
+        let mut control = [0; LED_CONTROL_LEN];
+        for (y, row) in control.chunks_mut(2).enumerate() {
+            // Leftmost pixel goes to bit 0.
+            row[0] = row_bits(data, y as i16, 0, 8).reverse_bits();
+            row[1] = row_bits(data, y as i16, 8, 8).reverse_bits();
+        }
+
+        self.write_frame(frame, &control, |x, y| {
+            if is_on(&control, x, y) { pwm } else { 0 }
+        })
+    }
+
+    /// Output the top-left `WIDTH` x `HEIGHT` pixels to frame, with pixel level as PWM duty (0 - 255).
+    pub fn output_gray<S>(&mut self, frame: u8, pixels: &S) -> Result<(), DeviceError<E>>
+        where
+            S: GrayPixels
+    {
+        let mut control = [0; LED_CONTROL_LEN];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if pixels.level(x as i16, y as i16) > 0 {
+                    control[y * 2 + x / 8] |= 1 << (x % 8);
+                }
+            }
+        }
+
+        self.write_frame(frame, &control, |x, y| pixels.level(x as i16, y as i16))
+    }
+
+    /// Write LED control registers and PWM registers of frame, PWM one row at a time.
+    fn write_frame<F>(&mut self, frame: u8, control: &[u8; LED_CONTROL_LEN], pwm: F) -> Result<(), DeviceError<E>>
+        where F: Fn(usize, usize) -> u8 {
+        check_frame(frame)?;
+        self.select(frame)?;
+
+        let mut bytes = [0; LED_CONTROL_LEN + 1];
+        bytes[0] = LED_CONTROL;
+        bytes[1..].copy_from_slice(control);
+        self.write(&bytes)?;
+
+        let mut bytes = [0; WIDTH + 1];
+        for y in 0..HEIGHT {
+            bytes[0] = PWM + (y * WIDTH) as u8;
+            for (x, value) in bytes[1..].iter_mut().enumerate() {
+                *value = pwm(x, y);
+            }
+            self.write(&bytes)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_function(&mut self, register: Register, values: &[u8]) -> Result<(), DeviceError<E>> {
+        self.select(FUNCTION_PAGE)?;
+
+        let mut bytes = [0; 3];
+        bytes[0] = register as u8;
+        bytes[1..=values.len()].copy_from_slice(values);
+        self.write(&bytes[..=values.len()])
+    }
+
+    /// Point the command register to page, unless it already is.
+    fn select(&mut self, page: u8) -> Result<(), DeviceError<E>> {
+        if self.page != Some(page) {
+            self.write(&[COMMAND, page])?;
+            self.page = Some(page);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), DeviceError<E>> {
+        let address = self.address as u8;
+        self.i2c.write(address, bytes).map_err(|e| self.bus_error(e))
+    }
+
+    /// The selected page is unknown after a failed transfer.
+    fn bus_error(&mut self, error: E) -> DeviceError<E> {
+        self.page = None;
+        DeviceError::Bus(error)
+    }
+}
+
+fn check_frame<E>(frame: u8) -> Result<(), DeviceError<E>> {
+    if frame >= FRAMES {
+        return Err(DeviceError::InvalidFrame(frame));
+    }
+    Ok(())
+}
+
+fn is_on(control: &[u8; LED_CONTROL_LEN], x: usize, y: usize) -> bool {
+    control[y * 2 + x / 8] & (1 << (x % 8)) != 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sim::Chip;
+    use bitcanvas::BitCanvas;
+    use bitcanvas::consts::{W16, H16};
+
+    fn device() -> Device<Chip> {
+        let mut device = Device::new(Address::Address00, Chip::new(Address::Address00));
+        device.init().unwrap();
+        device
+    }
+
+    #[test]
+    fn init_shows_first_frame() {
+        let device = device();
+        assert!(!device.i2c().is_shutdown());
+        assert_eq!(device.i2c().displayed_frame(), 0);
+        assert_eq!(device.i2c().page(), FUNCTION_PAGE);
+    }
+
+    #[test]
+    fn pixels_go_to_frame() {
+        let mut device = device();
+        let mut canvas = BitCanvas::<W16, H16>::new(16, 9).unwrap();
+        canvas.row_mut(0).unwrap().copy_from_slice(&[0b1000_0000, 0b0000_0001]);
+        canvas.row_mut(8).unwrap().copy_from_slice(&[0, 0b1000_0000]);
+        device.output_pixels(3, &canvas, 100).unwrap();
+
+        let chip = device.i2c();
+        assert!(chip.led(3, 0, 0));
+        assert!(chip.led(3, 15, 0));
+        assert!(chip.led(3, 8, 8));
+        assert_eq!(chip.pwm(3, 8, 8), 100);
+        assert_eq!(chip.pwm(3, 1, 0), 0);
+        assert_eq!(chip.lit_count(3), 3);
+        assert_eq!(chip.lit_count(0), 0);
+        assert_eq!(chip.brightness(8, 8), 0);
+
+        device.show_frame(3).unwrap();
+        assert_eq!(device.i2c().brightness(8, 8), 100);
+        assert_eq!(device.output_pixels(8, &canvas, 1), Err(DeviceError::InvalidFrame(8)));
+    }
+
+    #[test]
+    fn gray_levels_set_pwm() {
+        let mut device = device();
+        device.output_gray(0, &|x: i16, y: i16| (x * 16 + y) as u8).unwrap();
+
+        let chip = device.i2c();
+        assert_eq!(chip.brightness(15, 8), 248);
+        assert_eq!(chip.brightness(1, 2), 18);
+        assert!(!chip.led(0, 0, 0));
+        assert_eq!(chip.lit_count(0), 143);
+    }
+
+    #[test]
+    fn auto_play_and_breath() {
+        let mut device = device();
+        device.set_auto_play(AutoPlay::new(1, 3).frame_delay_ms(110)).unwrap();
+        assert_eq!(device.i2c().function(Register::Config), 0b01_001);
+        assert_eq!(device.i2c().function(Register::AutoPlay1), 3);
+        assert_eq!(device.i2c().function(Register::AutoPlay2), 10);
+        assert_eq!(device.current_frame(), Ok(1));
+
+        device.set_breath(Some(Breath { fade_in: 1, fade_out: 2, extinguish: 3 })).unwrap();
+        assert_eq!(device.i2c().function(Register::Breath1), 0b010_0001);
+        assert_eq!(device.i2c().function(Register::Breath2), 0b1_0011);
+        device.set_breath(None).unwrap();
+        assert_eq!(device.i2c().function(Register::Breath2), 0);
+    }
+
+    #[test]
+    fn page_is_selected_once() {
+        let mut device = device();
+        let writes = device.i2c().write_count();
+        device.clear_frame(2).unwrap();
+        device.clear_frame(2).unwrap();
+        // Page select, LED control and 9 PWM rows, then again without page select.
+        assert_eq!(device.i2c().write_count(), writes + 11 + 10);
+    }
+}
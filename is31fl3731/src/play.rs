@@ -0,0 +1,114 @@
+use crate::DeviceError;
+use crate::register::{MODE_AUTO_PLAY, BREATH_ENABLE};
+
+/// Frame delay step of auto play in ms.
+const FRAME_DELAY_STEP_MS: u16 = 11;
+/// Longest frame delay, in steps, encoded as 0.
+const FRAME_DELAY_MAX_STEPS: u16 = 64;
+
+/// Frames played one after another by the chip.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AutoPlay {
+    /// First frame (0 - 7).
+    pub first_frame: u8,
+    /// Number of frames (1 - 8), wrapping around after frame 7.
+    pub frames: u8,
+    /// Number of loops (1 - 7), 0 plays endlessly.
+    pub loops: u8,
+    /// Delay between frames in ms, rounded down to 11 ms steps and limited to 11 - 704.
+    pub frame_delay_ms: u16,
+}
+
+impl AutoPlay {
+    /// Play frames endlessly with the shortest delay.
+    pub fn new(first_frame: u8, frames: u8) -> AutoPlay {
+        AutoPlay {
+            first_frame,
+            frames,
+            loops: 0,
+            frame_delay_ms: FRAME_DELAY_STEP_MS,
+        }
+    }
+
+    pub fn loops(mut self, loops: u8) -> AutoPlay {
+        self.loops = loops;
+        self
+    }
+
+    pub fn frame_delay_ms(mut self, frame_delay_ms: u16) -> AutoPlay {
+        self.frame_delay_ms = frame_delay_ms;
+        self
+    }
+
+    /// Values of configuration, auto play control 1 and auto play control 2 registers.
+    pub(crate) fn registers<E>(&self) -> Result<[u8; 3], DeviceError<E>> {
+        if self.first_frame > 7 {
+            return Err(DeviceError::InvalidFrame(self.first_frame));
+        }
+        if self.frames == 0 || self.frames > 8 {
+            return Err(DeviceError::InvalidSetting(self.frames));
+        }
+        if self.loops > 7 {
+            return Err(DeviceError::InvalidSetting(self.loops));
+        }
+
+        let steps = (self.frame_delay_ms / FRAME_DELAY_STEP_MS).clamp(1, FRAME_DELAY_MAX_STEPS);
+
+        Ok([
+            MODE_AUTO_PLAY | self.first_frame,
+            (self.loops << 4) | (self.frames % 8),
+            (steps % FRAME_DELAY_MAX_STEPS) as u8,
+        ])
+    }
+}
+
+/// Breathing of the shown frame. Times are exponents, 0 - 7.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Breath {
+    /// Fade in time, 26 ms x 2^n.
+    pub fade_in: u8,
+    /// Fade out time, 26 ms x 2^n.
+    pub fade_out: u8,
+    /// Time the LEDs stay off between breaths, 3.5 ms x 2^n.
+    pub extinguish: u8,
+}
+
+impl Breath {
+    /// Values of breath control 1 and breath control 2 registers.
+    pub(crate) fn registers<E>(&self) -> Result<[u8; 2], DeviceError<E>> {
+        for &time in &[self.fade_in, self.fade_out, self.extinguish] {
+            if time > 7 {
+                return Err(DeviceError::InvalidSetting(time));
+            }
+        }
+
+        Ok([
+            (self.fade_out << 4) | self.fade_in,
+            BREATH_ENABLE | self.extinguish,
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn auto_play_registers() {
+        let play = AutoPlay::new(2, 8).loops(3).frame_delay_ms(100);
+        assert_eq!(play.registers::<()>(), Ok([0b01_010, 0b011_0000, 9]));
+
+        assert_eq!(AutoPlay::new(0, 1).frame_delay_ms(704).registers::<()>(), Ok([0b01_000, 1, 0]));
+        assert_eq!(AutoPlay::new(0, 1).frame_delay_ms(0).registers::<()>(), Ok([0b01_000, 1, 1]));
+        assert_eq!(AutoPlay::new(8, 1).registers::<()>(), Err(DeviceError::InvalidFrame(8)));
+        assert_eq!(AutoPlay::new(0, 0).registers::<()>(), Err(DeviceError::InvalidSetting(0)));
+        assert_eq!(AutoPlay::new(0, 1).loops(8).registers::<()>(), Err(DeviceError::InvalidSetting(8)));
+    }
+
+    #[test]
+    fn breath_registers() {
+        let breath = Breath { fade_in: 3, fade_out: 5, extinguish: 1 };
+        assert_eq!(breath.registers::<()>(), Ok([0b101_0011, 0b1_0001]));
+        assert_eq!(Breath { fade_in: 8, ..breath }.registers::<()>(), Err(DeviceError::InvalidSetting(8)));
+    }
+}
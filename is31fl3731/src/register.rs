@@ -0,0 +1,55 @@
+/// Command Register, selects the page following writes go to
+pub const COMMAND: u8 = 0xfd;
+/// Page of the function registers
+pub const FUNCTION_PAGE: u8 = 0x0b;
+
+/// LED Control Registers, on or off state of each LED, 2 per row
+pub const LED_CONTROL: u8 = 0x00;
+pub const LED_CONTROL_LEN: usize = 18;
+/// PWM Registers, 8 bit duty of each LED, 16 per row
+pub const PWM: u8 = 0x24;
+pub const PWM_LEN: usize = 144;
+/// Registers in a frame page
+pub const FRAME_LEN: usize = PWM as usize + PWM_LEN;
+
+/// Registers of the function page.
+#[derive(Copy, Clone)]
+#[repr(u8)]
+pub enum Register {
+    /// Configuration Register, display mode and auto play start frame, default 0000 0000
+    Config = 0x00,
+    /// Picture Display Register, frame shown in picture mode, default 0000 0000
+    PictureDisplay = 0x01,
+    /// Auto Play Control Register 1, number of loops and frames, default 0000 0000
+    AutoPlay1 = 0x02,
+    /// Auto Play Control Register 2, frame delay time, default 0000 0000
+    AutoPlay2 = 0x03,
+    /// Display Option Register, intensity control and blink, default 0000 0000
+    DisplayOption = 0x05,
+    /// Audio Synchronization Register, default 0000 0000
+    AudioSync = 0x06,
+    /// Frame State Register, read only, current frame
+    FrameState = 0x07,
+    /// Breath Control Register 1, fade in and fade out time, default 0000 0000
+    Breath1 = 0x08,
+    /// Breath Control Register 2, breath enable and extinguish time, default 0000 0000
+    Breath2 = 0x09,
+    /// Shutdown Register, 0 is software shutdown, default 0000 0000
+    Shutdown = 0x0a,
+    /// AGC Control Register, default 0000 0000
+    AgcControl = 0x0b,
+    /// Audio ADC Rate Register, default 0000 0000
+    AudioAdcRate = 0x0c,
+}
+
+/// Registers in the function page
+pub const FUNCTION_LEN: usize = 0x0d;
+
+/// Display mode bits of the configuration register.
+pub const MODE_PICTURE: u8 = 0b00_000;
+pub const MODE_AUTO_PLAY: u8 = 0b01_000;
+pub const MODE_MASK: u8 = 0b11_000;
+pub const FRAME_MASK: u8 = 0b111;
+
+/// Breath enable bit of the breath control register 2.
+pub const BREATH_ENABLE: u8 = 0b1_0000;
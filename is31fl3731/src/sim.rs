@@ -0,0 +1,233 @@
+//! Register-level simulator of a single IS31FL3731 chip.
+//!
+//! The command register selects the page following writes go to: one of the eight
+//! frames or the function registers. The first byte of a write selects a register
+//! of the page, following bytes are written to auto-incremented register addresses.
+//! Reads continue from the last selected register.
+
+use hal::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+use crate::register::{
+    Register, COMMAND, FUNCTION_PAGE, FUNCTION_LEN, FRAME_LEN,
+    LED_CONTROL, LED_CONTROL_LEN, PWM, MODE_AUTO_PLAY, MODE_MASK, FRAME_MASK,
+};
+use crate::{Address, FRAMES, WIDTH};
+
+/// Simulated chip error.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    /// Nobody acknowledged the address.
+    Nack(u8),
+    /// Write did not contain the register address.
+    EmptyWrite,
+    /// Command register pointed to a page the chip does not have.
+    InvalidPage(u8),
+    /// Write to the register the page does not have.
+    InvalidRegister(u8),
+}
+
+/// Simulated IS31FL3731 chip on the I2C bus.
+pub struct Chip {
+    address: u8,
+    page: u8,
+    register: u8,
+    frames: [[u8; FRAME_LEN]; FRAMES as usize],
+    function: [u8; FUNCTION_LEN],
+    writes: usize,
+}
+
+impl Chip {
+    pub fn new(address: Address) -> Chip {
+        Chip {
+            address: address as u8,
+            page: 0,
+            register: 0,
+            frames: [[0; FRAME_LEN]; FRAMES as usize],
+            function: [0; FUNCTION_LEN],
+            writes: 0,
+        }
+    }
+
+    /// Page selected by the command register.
+    pub fn page(&self) -> u8 {
+        self.page
+    }
+
+    /// Raw value of a function register.
+    pub fn function(&self, register: Register) -> u8 {
+        self.function[register as usize]
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.function(Register::Shutdown) & 1 == 0
+    }
+
+    /// Frame shown in picture mode, or the first frame in auto play mode.
+    pub fn displayed_frame(&self) -> u8 {
+        let config = self.function(Register::Config);
+        if config & MODE_MASK == MODE_AUTO_PLAY {
+            config & FRAME_MASK
+        } else {
+            self.function(Register::PictureDisplay) & FRAME_MASK
+        }
+    }
+
+    /// Check if LED at (x, y) of frame is turned on.
+    pub fn led(&self, frame: u8, x: usize, y: usize) -> bool {
+        self.frames[frame as usize][LED_CONTROL as usize + y * 2 + x / 8] & (1 << (x % 8)) != 0
+    }
+
+    /// PWM duty of LED at (x, y) of frame.
+    pub fn pwm(&self, frame: u8, x: usize, y: usize) -> u8 {
+        self.frames[frame as usize][PWM as usize + y * WIDTH + x]
+    }
+
+    /// Number of LEDs of frame that are turned on.
+    pub fn lit_count(&self, frame: u8) -> u32 {
+        self.frames[frame as usize][..LED_CONTROL_LEN].iter()
+            .map(|r| r.count_ones())
+            .sum()
+    }
+
+    /// PWM duty of the LED at (x, y) of the displayed frame, 0 if it is off.
+    pub fn brightness(&self, x: usize, y: usize) -> u8 {
+        let frame = self.displayed_frame();
+        if self.is_shutdown() || !self.led(frame, x, y) {
+            0
+        } else {
+            self.pwm(frame, x, y)
+        }
+    }
+
+    /// Number of writes addressed to this chip.
+    pub fn write_count(&self) -> usize {
+        self.writes
+    }
+
+    fn page_registers(&mut self) -> &mut [u8] {
+        match self.page {
+            FUNCTION_PAGE => &mut self.function,
+            frame => &mut self.frames[frame as usize],
+        }
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        if register == COMMAND {
+            if value >= FRAMES && value != FUNCTION_PAGE {
+                return Err(Error::InvalidPage(value));
+            }
+            self.page = value;
+            return Ok(());
+        }
+
+        let registers = self.page_registers();
+        match registers.get_mut(register as usize) {
+            Some(r) => *r = value,
+            None => return Err(Error::InvalidRegister(register)),
+        }
+        Ok(())
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, Error> {
+        if self.page == FUNCTION_PAGE && register == Register::FrameState as u8 {
+            return Ok(self.displayed_frame());
+        }
+        self.page_registers().get(register as usize).cloned().ok_or(Error::InvalidRegister(register))
+    }
+}
+
+impl Chip {
+    /// Decode a single write transfer.
+    fn transfer(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        if address != self.address {
+            return Err(Error::Nack(address));
+        }
+
+        self.writes += 1;
+
+        let (register, data) = bytes.split_first().ok_or(Error::EmptyWrite)?;
+
+        self.register = *register;
+        for value in data {
+            self.write_register(self.register, *value)?;
+            self.register = self.register.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    /// Read from the last selected register on.
+    fn receive(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        if address != self.address {
+            return Err(Error::Nack(address));
+        }
+
+        for value in buffer {
+            *value = self.read_register(self.register)?;
+            self.register = self.register.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    fn operations(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => self.transfer(address, bytes)?,
+                Operation::Read(buffer) => self.receive(address, buffer)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl hal::i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match *self {
+            Error::Nack(_) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            Error::EmptyWrite | Error::InvalidPage(_) | Error::InvalidRegister(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl hal::i2c::ErrorType for Chip {
+    type Error = Error;
+}
+
+impl hal::i2c::I2c for Chip {
+    fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.operations(address, operations)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hal::i2c::I2c;
+
+    #[test]
+    fn writes_go_to_selected_page() {
+        let mut chip = Chip::new(Address::Address11);
+        let address = Address::Address11 as u8;
+        chip.write(address, &[COMMAND, 5]).unwrap();
+        chip.write(address, &[PWM, 1, 2]).unwrap();
+        assert_eq!((chip.pwm(5, 0, 0), chip.pwm(5, 1, 0)), (1, 2));
+        assert_eq!(chip.pwm(0, 0, 0), 0);
+
+        assert_eq!(chip.write(address, &[COMMAND, 9]), Err(Error::InvalidPage(9)));
+        assert_eq!(chip.write(address, &[COMMAND, FUNCTION_PAGE]), Ok(()));
+        assert_eq!(chip.write(address, &[0x0d, 1]), Err(Error::InvalidRegister(0x0d)));
+        assert_eq!(chip.write(0x74, &[COMMAND, 0]), Err(Error::Nack(0x74)));
+    }
+
+    #[test]
+    fn reads_continue_from_register() {
+        let mut chip = Chip::new(Address::Address00);
+        let address = Address::Address00 as u8;
+        chip.write(address, &[COMMAND, FUNCTION_PAGE]).unwrap();
+        chip.write(address, &[Register::PictureDisplay as u8, 6]).unwrap();
+
+        let mut state = [0; 2];
+        chip.write_read(address, &[Register::FrameState as u8], &mut state).unwrap();
+        assert_eq!(state, [6, 0]);
+    }
+}
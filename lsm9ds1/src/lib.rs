@@ -4,18 +4,24 @@
 
 extern crate embedded_hal as hal;
 
+#[cfg(feature = "eh02")]
+pub use i2c_compat as eh02;
+
 mod register {
     #[derive(Copy, Clone)]
     #[repr(u8)]
     pub enum Register {
-        /// Configuration Register, default 0000 0000
-        Config = 0x00,
+        /// Angular rate sensor Control Register 1, default 0000 0000
+        CtrlReg1G = 0x10,
     }
 }
 
+/// Gyroscope output data rate of 119 Hz in bits 7 - 5, zero powers the gyroscope down.
+const GYRO_ODR_119HZ: u8 = 0b0110_0000;
+
 pub struct Device<I2C>
     where
-        I2C: hal::blocking::i2c::Write,
+        I2C: hal::i2c::I2c,
 {
     acc_gyro_address: u8,
     i2c: I2C,
@@ -23,16 +29,61 @@ pub struct Device<I2C>
 
 impl<I2C, E> Device<I2C>
     where
-        I2C: hal::blocking::i2c::Write<Error = E>,
+        I2C: hal::i2c::I2c<Error = E>,
 {
     pub fn new(i2c: I2C) -> Device<I2C> {
         Device {
-            acc_gyro_address: 0b110101_0, // last bit is external
+            acc_gyro_address: 0b110_1010, // last bit is external
             i2c,
         }
     }
 
     pub fn set_gyro_on(&mut self, value: bool) -> Result<(), E> {
-        self.i2c.write(self.acc_gyro_address as u8, &[register::Register::Pwm as u8, (value & 0b0111_1111) | 0b1000_0000])
+        let odr = if value { GYRO_ODR_119HZ } else { 0 };
+        self.i2c.write(self.acc_gyro_address, &[register::Register::CtrlReg1G as u8, odr])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hal::i2c::{ErrorKind, Operation};
+
+    /// Bus that records the last write.
+    #[derive(Default)]
+    struct Bus {
+        address: u8,
+        written: [u8; 2],
+    }
+
+    impl hal::i2c::ErrorType for Bus {
+        type Error = ErrorKind;
+    }
+
+    impl hal::i2c::I2c for Bus {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => {
+                        self.address = address;
+                        self.written.copy_from_slice(bytes);
+                    },
+                    Operation::Read(_) => return Err(ErrorKind::Other),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn gyro_sets_data_rate() {
+        let mut device = Device::new(Bus::default());
+
+        device.set_gyro_on(true).unwrap();
+        assert_eq!(device.i2c.address, 0b110_1010);
+        assert_eq!(device.i2c.written, [0x10, 0b0110_0000]);
+
+        device.set_gyro_on(false).unwrap();
+        assert_eq!(device.i2c.written, [0x10, 0]);
     }
 }
\ No newline at end of file
@@ -5,3 +5,5 @@
 
 extern crate embedded_hal as hal;
 
+#[cfg(feature = "eh02")]
+pub use i2c_compat as eh02;
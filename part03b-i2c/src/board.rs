@@ -1,99 +1,115 @@
-use embedded_hal as hal;
+use embedded_hal_1 as hal;
 use is31fl3730 as isd;
 use isd::pixels::DataBits;
-use isd::display::OutputRows;
+use isd::display::MatrixBank;
+use isd::layout::{Placement, Rotation};
 
 pub struct Screen<I2C1, E1, I2C2, E2>
     where
         E1: core::fmt::Debug,
         E2: core::fmt::Debug,
-        I2C1: hal::blocking::i2c::Write<Error = E1>,
-        I2C2: hal::blocking::i2c::Write<Error = E2>,
+        I2C1: hal::i2c::I2c<Error = E1>,
+        I2C2: hal::i2c::I2c<Error = E2>,
 {
-    m1: isd::Device<I2C1>,
-    m1_should_reload: bool,
-    m2: isd::Device<I2C2>,
-    m2_should_reload: bool,
+    m1: isd::Resilient<I2C1, Setup>,
+    m2: isd::Resilient<I2C2, Setup>,
+    idle: Option<isd::IdleBlank>,
+    changed: bool,
 }
 
-fn restart<I2C, E>(device: &mut isd::Device<I2C>) -> Result<(), E>
+/// Configuration sent to both chips after every reset.
+pub struct Setup;
+
+impl<I2C, E> isd::Recipe<I2C> for Setup
+    where
+        I2C: hal::i2c::I2c<Error = E>
+{
+    fn apply(&mut self, device: &mut isd::Device<I2C>) -> Result<(), isd::DeviceError<E>> {
+        device.modify_lighting(|c|
+            c.set_current(isd::LightingCurrent::Current20mA))?;
+        device.modify_config(|c|
+            c.set_display_mode(isd::ConfigDisplayMode::Matrix1and2))
+    }
+}
+
+fn contents<I2C>(device: &isd::Device<I2C>) -> [[u8; 11]; 2]
     where
-        E: core::fmt::Debug,
-        I2C: hal::blocking::i2c::Write<Error = E>
+        I2C: hal::i2c::I2c
 {
-    device.reset()?;
-    device.modify_lighting(|c|
-        c.set_current(isd::LightingCurrent::Current20mA))?;
-    device.modify_config(|c|
-        c.set_display_mode(isd::ConfigDisplayMode::Matrix1and2))?;
-    Ok(())
+    let mut contents = [[0; 11]; 2];
+    contents[0].copy_from_slice(device.matrix1_rows());
+    contents[1].copy_from_slice(device.matrix2_rows());
+    contents
 }
 
+/// Two chips, each driving a mirrored 8x8 matrix followed by a rotated one.
+const LAYOUT: isd::layout::Layout<'static> = isd::layout::Layout {
+    matrix_mode: isd::ConfigMatrixMode::Size8x8,
+    placements: &[
+        Placement { address: isd::Address::Address11, bank: MatrixBank::Matrix1, x: 0, y: 0, rotation: Rotation::Deg0, mirror: true },
+        Placement { address: isd::Address::Address11, bank: MatrixBank::Matrix2, x: 8, y: 0, rotation: Rotation::Deg270, mirror: false },
+        Placement { address: isd::Address::Address01, bank: MatrixBank::Matrix1, x: 16, y: 0, rotation: Rotation::Deg0, mirror: true },
+        Placement { address: isd::Address::Address01, bank: MatrixBank::Matrix2, x: 24, y: 0, rotation: Rotation::Deg270, mirror: false },
+    ],
+};
+
 impl<I2C1, E1, I2C2, E2> Screen<I2C1, E1, I2C2, E2>
     where
         E1: core::fmt::Debug,
         E2: core::fmt::Debug,
-        I2C1: hal::blocking::i2c::Write<Error = E1>,
-        I2C2: hal::blocking::i2c::Write<Error = E2>,
+        I2C1: hal::i2c::I2c<Error = E1>,
+        I2C2: hal::i2c::I2c<Error = E2>,
 {
     pub fn new(i2c1: I2C1, i2c2: I2C2) -> Screen<I2C1, E1, I2C2, E2> {
+        let policy = isd::RetryPolicy::default();
         Screen {
-            m1: isd::Device::new(isd::Address::Address11, i2c1),
-            m1_should_reload: true,
-            m2: isd::Device::new(isd::Address::Address01, i2c2),
-            m2_should_reload: true,
+            m1: isd::Resilient::new(isd::Device::new(isd::Address::Address11, i2c1), policy, Setup),
+            m2: isd::Resilient::new(isd::Device::new(isd::Address::Address01, i2c2), policy, Setup),
+            idle: None,
+            changed: false,
         }
     }
 
-    fn render1<C>(&mut self, canvas: &C) -> Result<(), E1> where C: DataBits {
-        isd::display::MatrixTargetPrimary8x8{}
-            .output_pixels(&mut self.m1,
-                           &canvas
-                               .flip_h()
-                               .offset_bytes(1, 0)
-            )?;
-        isd::display::MatrixTargetSecondary8x8{}
-            .output_pixels(&mut self.m1,
-                           &canvas
-                               .offset_bytes(-1, 0)
-                               .rotate_90()
-            )?;
-        self.m1.update()?;
-        Ok(())
+    /// Blank the screen after its content stays unchanged for the specified time.
+    pub fn set_blank_after(&mut self, seconds: u16) {
+        self.idle = Some(isd::IdleBlank::new(seconds));
     }
 
-    fn render2<C>(&mut self, canvas: &C) -> Result<(), E2> where C: DataBits {
-        isd::display::MatrixTargetPrimary8x8{}
-            .output_pixels(&mut self.m2,
-                           &canvas
-                               .flip_h()
-                               .offset_bytes(3, 0)
-            )?;
-        isd::display::MatrixTargetSecondary8x8{}
-            .output_pixels(&mut self.m2,
-                           &canvas
-                               .offset_bytes(-3, 0)
-                               .rotate_90()
-            )?;
-        self.m2.update()?;
-        Ok(())
+    /// Error counters of both chips.
+    pub fn stats(&self) -> (isd::Stats, isd::Stats) {
+        (self.m1.stats(), self.m2.stats())
     }
 
-    pub fn render<C>(&mut self, canvas: &C) where C: DataBits {
-        if self.m1_should_reload {
-            if let Ok(()) = restart(&mut self.m1) {
-                self.m1_should_reload = self.render1(canvas).is_err();
-            }
-        } else {
-            self.m1_should_reload = self.render1(canvas).is_err()
-        }
+    /// Advance the blanking and retry timers.
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        let changed = self.changed;
+        self.changed = false;
 
-        if self.m2_should_reload {
-            if let Ok(()) = restart(&mut self.m2) {
-                self.m2_should_reload = self.render2(canvas).is_err();
+        self.m1.tick(elapsed_ms);
+        self.m2.tick(elapsed_ms);
+
+        if let Some(idle) = self.idle.as_mut() {
+            // Failures are counted and recovered by the next operation.
+            match idle.tick(elapsed_ms, changed) {
+                isd::IdleAction::Blank => {
+                    let _ = self.m1.run(|d| d.shutdown());
+                    let _ = self.m2.run(|d| d.shutdown());
+                },
+                isd::IdleAction::Wake => {
+                    let _ = self.m1.run(|d| d.wake());
+                    let _ = self.m2.run(|d| d.wake());
+                },
+                isd::IdleAction::None => (),
             }
-        } else {
-            self.m2_should_reload = self.render2(canvas).is_err()
         }
     }
+
+    pub fn render<C>(&mut self, canvas: &C) where C: DataBits {
+        let before = (contents(self.m1.device()), contents(self.m2.device()));
+
+        let _ = self.m1.run(|d| LAYOUT.output_pixels(d, canvas));
+        let _ = self.m2.run(|d| LAYOUT.output_pixels(d, canvas));
+
+        self.changed |= before != (contents(self.m1.device()), contents(self.m2.device()));
+    }
 }
@@ -25,6 +25,7 @@ use bitcanvas::BitCanvas;
 use bitcanvas::consts::*;
 use embedded_graphics::prelude::*;
 use profont::ProFont7Point;
+use is31fl3730::eh02::Compat;
 
 mod board;
 
@@ -66,9 +67,10 @@ fn main() -> ! {
     );
     let bus = shared_bus::CortexMBusManager::new(i2c);
 
-    let mut gyro = lsm::Device::new(bus.acquire());
+    let mut gyro = lsm::Device::new(Compat::new(bus.acquire()));
 
-    let mut screen = board::Screen::new(bus.acquire(), bus.acquire());
+    let mut screen = board::Screen::new(Compat::new(bus.acquire()), Compat::new(bus.acquire()));
+    screen.set_blank_after(60);
     let mut led = gpioa.pa1.into_push_pull_output(&mut gpioa.crl);
     let mut timer = Timer::syst(cp.SYST, 10.hz(), clocks);
 
@@ -92,6 +94,7 @@ fn main() -> ! {
         value += 112;
 
         screen.render(&canvas);
+        screen.tick(200);
 
         block!(timer.wait()).unwrap();
         led.set_high();